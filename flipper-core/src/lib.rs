@@ -1,4 +1,21 @@
 use parity_scale_codec::{Decode, Encode, HasCompact};
+use sp_core::crypto::{Ss58AddressFormat, Ss58Codec};
+
+/// The runtime's address format, which its `PropertiesApi` and chain specs report.
+pub use node_template_runtime::SS58_PREFIX;
+
+/// The address of `public` on the chain.
+pub fn format_address(public: &sp_core::sr25519::Public) -> String {
+	public.to_ss58check_with_version(Ss58AddressFormat::custom(SS58_PREFIX))
+}
+
+/// The account behind an address formatted with [`format_address`].
+pub fn parse_address(address: &str) -> Option<sp_core::sr25519::Public> {
+	sp_core::sr25519::Public::from_ss58check_with_version(address)
+		.ok()
+		.filter(|(_, format)| u16::from(*format) == SS58_PREFIX)
+		.map(|(public, _)| public)
+}
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct AsCompact<T: HasCompact>(#[codec(compact)] T);
@@ -10,25 +27,44 @@ pub enum Call {
 	Multiply(AsCompact<u32>),
 	Upgrade { password: Vec<u8>, payload: Vec<u8> },
 	Kill { password: Vec<u8> },
+	AuthorizeUpgrade { code_hash: sp_core::H256, enact_at: u32 },
+	ApplyAuthorizedUpgrade { code: Vec<u8> },
+	Pause,
+	Resume,
+	SetAdmins { admins: Vec<sp_core::H256>, threshold: u32 },
+	SetAuthorities { aura: Vec<sp_core::H256>, grandpa: Vec<sp_core::H256> },
+	Propose { call: Box<Call> },
+	Approve { id: u32 },
+	Batch(Vec<Call>),
+	BatchAll(Vec<Call>),
+	Schedule { when: u32, call: Box<Call> },
+	CancelScheduled { id: u32 },
+	SetBlockReward { reward: u128 },
+	Heartbeat { block_number: u32, authority_index: u32, signature: sp_core::sr25519::Signature },
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::{AsCompact, Call};
-	use parity_scale_codec::{Decode, Encode};
-	use sp_core::Pair;
+	use crate::{format_address, parse_address, AsCompact, Call};
+	use node_template_runtime::VERSION;
+	use parity_scale_codec::{Compact, Encode};
+	use sp_core::{crypto::Ss58Codec, Pair};
 	use std::io::Read;
 
-	type Address = sp_core::H256;
-	type Signature = sp_core::H512;
-
 	const ADMIN_SEED: &str =
 		"dignity fatal coconut isolate evolve cloth scorpion squirrel sentence gate chase olympic";
 
-	#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
-	struct TestExtrinsic {
-		call: Call,
-		signature: (Address, Signature),
+	/// The low bits of the version byte which starts every extrinsic.
+	const EXTRINSIC_FORMAT_VERSION: u8 = 4;
+	/// Set in the version byte of signed extrinsics.
+	const SIGNED_BIT: u8 = 0b1000_0000;
+	/// Prefixes every call, as the index of the pallet it belongs to.
+	const FLIPPER_PALLET_INDEX: u8 = 1;
+
+	/// The chain's genesis hash, which signatures cover: `GENESIS_HASH=0x... cargo test`.
+	fn genesis_hash() -> sp_core::H256 {
+		let hash = std::env::var("GENESIS_HASH").unwrap_or_default();
+		hash.trim_start_matches("0x").parse().unwrap_or_default()
 	}
 
 	fn output(value: &[u8]) -> String {
@@ -41,53 +77,97 @@ mod tests {
 		)
 	}
 
-	fn author_submit_extrinsic(
-		call: &[u8],
-		public_key: sp_core::sr25519::Public,
-		signature: sp_core::sr25519::Signature,
-		nonce: u32,
-	) -> String {
-		let address = sp_core::H256(public_key.0);
-		let signature = sp_core::H512(signature.0);
-		assert!(sp_io::crypto::sr25519_verify(
-			&sp_core::sr25519::Signature::from_raw(signature.0),
-			call,
-			&sp_core::sr25519::Public::from_raw(address.0)
-		));
-
+	fn submit(extrinsic: &[u8]) -> String {
 		format!(
 			r#"curl http://localhost:9933 -H "Content-Type:application/json;charset=utf-8" -d '{{
 	"jsonrpc":"2.0",
 	"id":1,
 	"method":"author_submitExtrinsic",
-	"params": ["0x{}{}"]
+	"params": ["0x{}"]
 }}'"#,
-			output(call),
-			output(&(address, signature, AsCompact(nonce)).encode())
+			output(extrinsic)
 		)
 	}
 
+	/// Prefixes `call` like the runtime's `RuntimeCall`.
+	fn runtime_call(call: &[u8]) -> Vec<u8> {
+		[&[FLIPPER_PALLET_INDEX][..], call].concat()
+	}
+
+	fn author_submit_unsigned(call: &[u8]) -> String {
+		let extrinsic = [&[EXTRINSIC_FORMAT_VERSION][..], &runtime_call(call)].concat();
+		submit(&extrinsic.encode())
+	}
+
+	/// Signs `call` like `sp_runtime::generic::UncheckedExtrinsic`, with an immortal era and no
+	/// tip.
+	fn author_submit_extrinsic(call: &[u8], pair: &sp_core::sr25519::Pair, nonce: u32) -> String {
+		let call = &runtime_call(call);
+		let genesis = genesis_hash();
+		// Era, nonce and tip.
+		let extra = (0u8, Compact(nonce), Compact(0u128)).encode();
+		// The runtime's versions, and an immortal era, which starts at genesis.
+		let additional =
+			(VERSION.spec_version, VERSION.transaction_version, genesis, genesis).encode();
+		let mut payload = [call, &extra, &additional].concat();
+		if payload.len() > 256 {
+			payload = sp_core::hashing::blake2_256(&payload).to_vec();
+		}
+		let signature = pair.sign(&payload);
+		assert!(sp_io::crypto::sr25519_verify(&signature, &payload, &pair.public()));
+
+		// `MultiAddress::Id` and `MultiSignature::Sr25519`.
+		let signer = (0u8, pair.public().0, 1u8, signature.0).encode();
+		let version = [EXTRINSIC_FORMAT_VERSION | SIGNED_BIT];
+		let extrinsic = [&version[..], &signer, &extra, call].concat();
+		format!("# Signed by {}\n{}", format_address(&pair.public()), submit(&extrinsic.encode()))
+	}
+
+	#[test]
+	fn formats_addresses_with_the_chain_prefix() {
+		let alice = sp_core::sr25519::Pair::from_string("//Alice", None).unwrap().public();
+		let address = format_address(&alice);
+		assert_eq!(parse_address(&address), Some(alice));
+		assert_eq!(parse_address(&alice.to_ss58check_with_version(42u16.into())), None);
+	}
+
 	#[test]
 	fn encode_flip() {
 		let pair: sp_core::sr25519::Pair = sp_core::Pair::generate().0;
 		let call = Call::Flip.encode();
-		println!("{}", author_submit_extrinsic(&call, pair.public(), pair.sign(&call), 1))
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
 	}
 
 	#[test]
 	fn encode_add() {
 		let pair: sp_core::sr25519::Pair = sp_core::Pair::generate().0;
 		let call = Call::Add(AsCompact(5)).encode();
-		println!("{}", author_submit_extrinsic(&call, pair.public(), pair.sign(&call), 1))
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
 	}
 
 	#[test]
 	fn encode_multiply() {
 		let pair: sp_core::sr25519::Pair = sp_core::Pair::generate().0;
 		let call = Call::Multiply(AsCompact(128)).encode();
-		println!("{}", author_submit_extrinsic(&call, pair.public(), pair.sign(&call), 0))
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
+	}
+
+	#[test]
+	fn encode_batch_all() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::generate().0;
+		let call =
+			Call::BatchAll(vec![Call::Add(AsCompact(5)), Call::Multiply(AsCompact(2))]).encode();
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
+	}
+
+	#[test]
+	fn schedule_flip() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::generate().0;
+		let call = Call::Schedule { when: 1000, call: Box::new(Call::Flip) }.encode();
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
 	}
 
+	// The runtime refuses this payload as it is not a valid Wasm blob, see `upgrade_wasm_admin`.
 	#[test]
 	fn upgrade() {
 		let pair: sp_core::sr25519::Pair = sp_core::Pair::generate().0;
@@ -96,11 +176,10 @@ mod tests {
 			payload: "wasm_blob".to_string().into_bytes(),
 		}
 		.encode();
-		println!("{}", author_submit_extrinsic(&call, pair.public(), pair.sign(&call), 0))
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
 	}
 
-	#[test]
-	fn upgrade_wasm_admin() {
+	fn runtime_wasm() -> Vec<u8> {
 		let runtime = std::fs::File::open(
 			"/home/fb/PBA/flipper-runtime/target/release/wbuild/frameless-runtime/frameless_runtime.compact.compressed.wasm",
 		)
@@ -108,17 +187,54 @@ mod tests {
 		let mut reader = std::io::BufReader::new(runtime);
 		let mut payload = Vec::new();
 		reader.read_to_end(&mut payload).unwrap();
+		payload
+	}
 
+	#[test]
+	fn upgrade_wasm_admin() {
+		let payload = runtime_wasm();
 		let pair = sp_core::sr25519::Pair::from_phrase(ADMIN_SEED, None).unwrap().0;
-		let call =
-			Call::Upgrade { password: "obsolescence".to_string().into_bytes(), payload }.encode();
-		println!("{}", author_submit_extrinsic(&call, pair.public(), pair.sign(&call), 0))
+		let call = Call::Upgrade { password: "obsolescence".to_string().into_bytes(), payload };
+		let call = Call::Propose { call: Box::new(call) }.encode();
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
 	}
 
+	#[test]
+	fn authorize_upgrade_admin() {
+		let code_hash = sp_core::H256(sp_core::hashing::blake2_256(&runtime_wasm()));
+		let pair = sp_core::sr25519::Pair::from_phrase(ADMIN_SEED, None).unwrap().0;
+		let call = Call::AuthorizeUpgrade { code_hash, enact_at: 100 };
+		let call = Call::Propose { call: Box::new(call) }.encode();
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
+	}
+
+	// Needs no signature, the runtime only accepts the authorized code.
+	#[test]
+	fn apply_authorized_upgrade() {
+		let call = Call::ApplyAuthorizedUpgrade { code: runtime_wasm() }.encode();
+		println!("{}", author_submit_unsigned(&call))
+	}
+
+	#[test]
+	fn pauses() {
+		let pair = sp_core::sr25519::Pair::from_phrase(ADMIN_SEED, None).unwrap().0;
+		let call = Call::Pause.encode();
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
+	}
+
+	// Only takes effect while paused, once a second admin has approved the proposal.
 	#[test]
 	fn kills() {
 		let pair = sp_core::sr25519::Pair::from_phrase(ADMIN_SEED, None).unwrap().0;
-		let call = Call::Kill { password: "bye".to_string().into_bytes() }.encode();
-		println!("{}", author_submit_extrinsic(&call, pair.public(), pair.sign(&call), 0))
+		let call = Call::Kill { password: "bye".to_string().into_bytes() };
+		let call = Call::Propose { call: Box::new(call) }.encode();
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
+	}
+
+	#[test]
+	fn approves() {
+		let pair = sp_core::sr25519::Pair::from_string("//Alice", None).unwrap();
+		let call = Call::Approve { id: 0 }.encode();
+		println!("{}", author_submit_extrinsic(&call, &pair, 0))
 	}
 }
@@ -1,6 +1,15 @@
 use substrate_wasm_builder::WasmBuilder;
 
 fn main() {
+	// Read by the `v4` migration, see `migrations.rs`.
+	println!("cargo:rerun-if-env-changed=LEGACY_GENESIS_HASH");
+	if let Ok(hash) = std::env::var("LEGACY_GENESIS_HASH") {
+		let hex = hash.strip_prefix("0x").unwrap_or(&hash);
+		if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+			panic!("LEGACY_GENESIS_HASH is not a 32 byte hex hash: {hash}");
+		}
+	}
+
 	WasmBuilder::new()
 		.with_current_project()
 		.export_heap_base()
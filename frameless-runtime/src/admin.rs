@@ -0,0 +1,147 @@
+//! Admins and the pause switch.
+//!
+//! Admin calls are dispatched once [`Threshold`] of the [`Admins`] have approved them, see
+//! [`crate::multisig`]. Pausing is the exception: any single admin can pause the chain, after which
+//! only admin business (and uploads of authorized code) is accepted, while blocks keep being
+//! produced and the chain can still be upgraded. Killing the chain is only possible while paused,
+//! and always needs at least [`KILL_CONFIRMATIONS`] approvals.
+
+use crate::{dispatch::Origin, storage::StorageValue, Address, Call, EMOJI};
+use log::info;
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_std::prelude::*;
+
+/// The minimum number of distinct admins which have to approve `Call::Kill`.
+pub const KILL_CONFIRMATIONS: usize = 2;
+
+/// `InvalidTransaction::Custom` code for calls refused while the chain is paused.
+pub const PAUSED: u8 = 1;
+
+storage_value! {
+	/// The accounts allowed to propose and approve admin calls.
+	pub struct Admins(b"Admin", b"Admins"): Vec<Address>;
+}
+
+storage_value! {
+	/// The number of admin approvals needed to dispatch a proposal.
+	pub struct Threshold(b"Admin", b"Threshold"): u32;
+}
+
+storage_value! {
+	/// Whether the chain is paused.
+	pub struct Paused(b"Admin", b"Paused"): bool;
+}
+
+/// Why a new admin set was refused.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum AdminError {
+	/// The threshold has to be between one and the number of admins.
+	InvalidThreshold,
+	DuplicateAdmin,
+}
+
+pub fn is_admin(who: &Address) -> bool {
+	Admins::get().unwrap_or_default().contains(who)
+}
+
+pub fn is_paused() -> bool {
+	Paused::get().unwrap_or(false)
+}
+
+impl Call {
+	/// Whether the call may still be dispatched by `origin` while the chain is paused.
+	///
+	/// Uploads of authorized code are open to anyone, all other admin business to admins only.
+	pub fn is_allowed_while_paused(&self, origin: &Origin) -> bool {
+		let by_admin = match origin {
+			Origin::Signed(who) => is_admin(who),
+			Origin::Admin => true,
+			Origin::None => false,
+		};
+		match self {
+			Call::Batch(calls) | Call::BatchAll(calls) =>
+				calls.iter().all(|call| call.is_allowed_while_paused(origin)),
+			Call::ApplyAuthorizedUpgrade { .. } => true,
+			Call::Propose { .. } | Call::Approve { .. } => by_admin,
+			call => by_admin && call.is_admin_call(),
+		}
+	}
+}
+
+/// Checks that `threshold` of `admins` is a satisfiable, duplicate free signer set.
+pub fn check_admins(admins: &[Address], threshold: u32) -> Result<(), AdminError> {
+	if threshold == 0 || threshold as usize > admins.len() {
+		return Err(AdminError::InvalidThreshold)
+	}
+	if admins.iter().enumerate().any(|(i, a)| admins[..i].contains(a)) {
+		return Err(AdminError::DuplicateAdmin)
+	}
+	Ok(())
+}
+
+pub fn set_admins(admins: Vec<Address>, threshold: u32) -> Result<(), AdminError> {
+	check_admins(&admins, threshold)?;
+	info!(target: "admin", "{EMOJI} new admins: {threshold} of {}", admins.len());
+	Admins::put(&admins);
+	Threshold::put(&threshold);
+	Ok(())
+}
+
+pub fn pause() {
+	info!(target: "admin", "{EMOJI} chain paused");
+	Paused::put(&true);
+}
+
+pub fn resume() {
+	info!(target: "admin", "{EMOJI} chain resumed");
+	Paused::kill();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+
+	#[test]
+	fn admin_sets_are_checked() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let (alice, bob) = (H256::repeat_byte(1), H256::repeat_byte(2));
+
+			assert_eq!(set_admins(vec![alice, bob], 0), Err(AdminError::InvalidThreshold));
+			assert_eq!(set_admins(vec![alice, bob], 3), Err(AdminError::InvalidThreshold));
+			assert_eq!(set_admins(vec![alice, alice], 2), Err(AdminError::DuplicateAdmin));
+			assert_eq!(Admins::get(), None);
+
+			assert_eq!(set_admins(vec![alice, bob], 2), Ok(()));
+			assert!(is_admin(&bob));
+			assert_eq!(Threshold::get(), Some(2));
+		});
+	}
+
+	#[test]
+	fn only_admin_calls_while_paused() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let (alice, bob) = (H256::repeat_byte(1), H256::repeat_byte(2));
+			Admins::put(&vec![alice]);
+			let (admin, stranger) = (Origin::Signed(alice), Origin::Signed(bob));
+
+			assert!(!Call::Flip.is_allowed_while_paused(&admin));
+			assert!(Call::Resume.is_allowed_while_paused(&Origin::Admin));
+			assert!(Call::Approve { id: 0 }.is_allowed_while_paused(&admin));
+			assert!(Call::ApplyAuthorizedUpgrade { code: vec![] }
+				.is_allowed_while_paused(&Origin::None));
+			assert!(Call::Batch(vec![Call::Pause, Call::Approve { id: 0 }])
+				.is_allowed_while_paused(&admin));
+			assert!(!Call::BatchAll(vec![Call::Resume, Call::Flip]).is_allowed_while_paused(&admin));
+
+			// Only admins get their business through.
+			assert!(!Call::Approve { id: 0 }.is_allowed_while_paused(&stranger));
+			assert!(
+				!Call::Propose { call: Box::new(Call::Resume) }.is_allowed_while_paused(&stranger)
+			);
+			assert!(!Call::Pause.is_allowed_while_paused(&stranger));
+			assert!(!Call::Pause.is_allowed_while_paused(&Origin::None));
+		});
+	}
+}
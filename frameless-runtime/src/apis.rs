@@ -0,0 +1,47 @@
+//! Runtime APIs specific to this runtime.
+
+use crate::{AccountInfo, Address, BlockNumber, DispatchError, Scheduled};
+use sp_runtime::RuntimeString;
+use sp_std::prelude::*;
+
+sp_api::decl_runtime_apis! {
+	/// Inspect the calls waiting in the scheduler.
+	pub trait SchedulerApi {
+		/// Every scheduled call along with the block it is scheduled for, in block order.
+		fn pending() -> Vec<(BlockNumber, Scheduled)>;
+	}
+
+	/// The state of accounts, for wallets.
+	pub trait AccountApi {
+		fn account_info(who: Address) -> AccountInfo;
+	}
+
+	/// Explain why extrinsics failed.
+	pub trait ErrorApi {
+		/// The error of this runtime behind an error `apply_extrinsic` returned, or `None` if it
+		/// is not one of ours.
+		fn decode_error(error: sp_runtime::DispatchError) -> Option<DispatchError>;
+	}
+
+	/// How wallets should display balances and addresses, also found in the chain spec
+	/// properties.
+	pub trait PropertiesApi {
+		fn token_symbol() -> RuntimeString;
+		/// The number of decimals of a balance.
+		fn token_decimals() -> u8;
+		/// The prefix of the SS58 encoding of addresses.
+		fn ss58_prefix() -> u16;
+	}
+
+	/// Dry runs of runtime upgrades, see the node's `try-upgrade` subcommand. Only runtimes
+	/// built with the `try-runtime` feature implement it.
+	pub trait TryRuntime {
+		/// Runs the migrations pending since the last runtime upgrade, returning their names.
+		fn on_runtime_upgrade() -> Vec<RuntimeString>;
+
+		/// Executes `block` like `Core_execute_block`, but without checking the signatures of its
+		/// extrinsics unless `signature_check`: those of past blocks cover the `spec_version`
+		/// they were signed for, so they fail under an upgraded runtime.
+		fn execute_block(block: Block, signature_check: bool);
+	}
+}
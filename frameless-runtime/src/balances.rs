@@ -0,0 +1,68 @@
+//! Account balances.
+//!
+//! Balances are endowed at genesis. For now they only pay for transaction tips, see
+//! [`crate::extensions`].
+
+use crate::{storage::StorageMap, Address};
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+
+pub type Balance = u128;
+
+/// The symbol wallets show next to balances.
+pub const TOKEN_SYMBOL: &str = "FLIP";
+/// A balance of 1 is `10^-TOKEN_DECIMALS` tokens.
+pub const TOKEN_DECIMALS: u8 = 12;
+
+storage_map! {
+	/// The free balance of every account which has any.
+	pub struct Balances(b"Balances", b"Account"): map Address => Balance;
+}
+
+/// Why a balance could not be changed.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum BalanceError {
+	InsufficientBalance,
+}
+
+pub fn free_balance(who: &Address) -> Balance {
+	Balances::get(who).unwrap_or(0)
+}
+
+/// Creates `amount` in the balance of `who`, saturating.
+pub fn mint(who: &Address, amount: Balance) {
+	Balances::mutate(who, |balance| *balance = Some(balance.unwrap_or(0).saturating_add(amount)));
+}
+
+/// Takes `amount` out of the balance of `who`, removing the account once it is empty.
+pub fn withdraw(who: &Address, amount: Balance) -> Result<(), BalanceError> {
+	Balances::mutate(who, |balance| {
+		let remaining = balance
+			.unwrap_or(0)
+			.checked_sub(amount)
+			.ok_or(BalanceError::InsufficientBalance)?;
+		*balance = Some(remaining).filter(|b| *b > 0);
+		Ok(())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+
+	#[test]
+	fn withdraws() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let alice = H256::repeat_byte(1);
+			Balances::insert(&alice, &10);
+
+			assert_eq!(withdraw(&alice, 11), Err(BalanceError::InsufficientBalance));
+			assert_eq!(withdraw(&alice, 4), Ok(()));
+			assert_eq!(free_balance(&alice), 6);
+			assert_eq!(withdraw(&alice, 6), Ok(()));
+			assert!(!Balances::contains_key(&alice));
+			assert_eq!(withdraw(&H256::repeat_byte(2), 0), Ok(()));
+		});
+	}
+}
@@ -0,0 +1,372 @@
+//! Benchmarks of every call, run natively by the node's `benchmark` subcommand, whose results
+//! become [`crate::weights`].
+//!
+//! Each benchmark sets up the state its call needs in a fresh `TestExternalities`, then times the
+//! dispatch of the call and counts the storage reads and writes it makes, see
+//! [`crate::storage::tracking`]. Setups take the most expensive successful path they reasonably
+//! can, e.g. `Approve` on a proposal which already has approvals.
+//!
+//! The calls taking runtime code are benchmarked with real code, whose version the host reads with
+//! the executor the node passes in, at two lengths to find what each KiB adds.
+
+use crate::{
+	admin::{Admins, Paused, Threshold},
+	dispatch::{dispatch, Origin},
+	multisig,
+	offchain::Heartbeat,
+	storage::{tracking, StorageValue},
+	upgrade::{Authorized, AuthorizedUpgrade},
+	AsCompact, Call, CurrentHeader, Header, Value, KILL_PASSWORD, UPGRADE_PASSWORD,
+};
+use parity_scale_codec::{Decode, Encode};
+use sp_core::{
+	traits::{Externalities, ReadRuntimeVersion, ReadRuntimeVersionExt},
+	H256,
+};
+use sp_runtime::traits::Header as _;
+use sp_version::RuntimeVersion;
+use std::{fmt::Write, time::Instant};
+
+/// What a benchmark measured.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BenchmarkResult {
+	/// The `Call` variant.
+	pub name: &'static str,
+	/// The median time the dispatch took.
+	pub nanos: u64,
+	pub reads: u32,
+	pub writes: u32,
+	/// The time each KiB of code adds, for the calls taking runtime code.
+	pub nanos_per_kib: Option<u64>,
+}
+
+struct Benchmark {
+	name: &'static str,
+	/// Prepares the state, returning the call to dispatch and its origin.
+	setup: fn() -> (Call, Origin),
+}
+
+struct CodeBenchmark {
+	name: &'static str,
+	/// Prepares the state for the given code, returning the call to dispatch and its origin.
+	setup: fn(Vec<u8>) -> (Call, Origin),
+}
+
+const ALICE: H256 = H256::repeat_byte(1);
+const BOB: H256 = H256::repeat_byte(2);
+const CHARLIE: H256 = H256::repeat_byte(3);
+/// The block the calls are dispatched in.
+const NOW: u32 = 1;
+
+fn signed() -> Origin {
+	Origin::Signed(ALICE)
+}
+
+fn set_admins() {
+	Admins::put(&vec![ALICE, BOB, CHARLIE]);
+	Threshold::put(&3);
+}
+
+/// Every call not taking runtime code, with the variants in the order `Call` declares them.
+const BENCHMARKS: &[Benchmark] = &[
+	Benchmark { name: "Flip", setup: || (Call::Flip, signed()) },
+	Benchmark {
+		name: "Add",
+		setup: || {
+			Value::put(&1);
+			(Call::Add(AsCompact(1)), signed())
+		},
+	},
+	Benchmark {
+		name: "Multiply",
+		setup: || {
+			Value::put(&2);
+			(Call::Multiply(AsCompact(2)), signed())
+		},
+	},
+	Benchmark {
+		name: "Kill",
+		setup: || {
+			Paused::put(&true);
+			(Call::Kill { password: KILL_PASSWORD.to_vec() }, Origin::Admin)
+		},
+	},
+	Benchmark {
+		name: "AuthorizeUpgrade",
+		setup: || {
+			let code_hash = H256::repeat_byte(4);
+			(Call::AuthorizeUpgrade { code_hash, enact_at: NOW + 1 }, Origin::Admin)
+		},
+	},
+	Benchmark { name: "Pause", setup: || (Call::Pause, Origin::Admin) },
+	Benchmark {
+		name: "Resume",
+		setup: || {
+			Paused::put(&true);
+			(Call::Resume, Origin::Admin)
+		},
+	},
+	Benchmark {
+		name: "SetAdmins",
+		setup: || {
+			(Call::SetAdmins { admins: vec![ALICE, BOB, CHARLIE], threshold: 2 }, Origin::Admin)
+		},
+	},
+	Benchmark {
+		name: "SetAuthorities",
+		setup: || (Call::SetAuthorities { aura: vec![ALICE], grandpa: vec![BOB] }, Origin::Admin),
+	},
+	Benchmark {
+		name: "Propose",
+		setup: || {
+			set_admins();
+			(Call::Propose { call: Box::new(Call::Pause) }, signed())
+		},
+	},
+	Benchmark {
+		name: "Approve",
+		setup: || {
+			set_admins();
+			let id = multisig::propose(BOB, Call::Pause).expect("Bob is an admin; qed");
+			(Call::Approve { id }, signed())
+		},
+	},
+	Benchmark { name: "Batch", setup: || (Call::Batch(vec![]), signed()) },
+	Benchmark { name: "BatchAll", setup: || (Call::BatchAll(vec![]), signed()) },
+	Benchmark {
+		name: "Schedule",
+		setup: || (Call::Schedule { when: NOW + 1, call: Box::new(Call::Flip) }, signed()),
+	},
+	Benchmark {
+		name: "CancelScheduled",
+		setup: || {
+			let id = crate::scheduler::schedule(signed(), NOW + 1, Call::Flip, NOW)
+				.expect("scheduled for a future block; qed");
+			(Call::CancelScheduled { id }, signed())
+		},
+	},
+	Benchmark {
+		name: "SetBlockReward",
+		setup: || (Call::SetBlockReward { reward: 1 }, Origin::Admin),
+	},
+	Benchmark {
+		name: "Heartbeat",
+		setup: || {
+			Value::put(&1);
+			// Dispatching does not check the signature, `validate_unsigned` does.
+			let heartbeat = Heartbeat { block_number: NOW - 1, authority_index: 0 };
+			let signature = sp_core::sr25519::Signature::from_raw([0; 64]);
+			(Call::Heartbeat { heartbeat, signature }, Origin::None)
+		},
+	},
+];
+
+/// The calls taking runtime code.
+const CODE_BENCHMARKS: &[CodeBenchmark] = &[
+	CodeBenchmark {
+		name: "Upgrade",
+		setup: |code| {
+			(Call::Upgrade { password: UPGRADE_PASSWORD.to_vec(), payload: code }, Origin::Admin)
+		},
+	},
+	CodeBenchmark {
+		name: "ApplyAuthorizedUpgrade",
+		setup: |code| {
+			let code_hash = H256(sp_io::hashing::blake2_256(&code));
+			Authorized::put(&AuthorizedUpgrade { code_hash, enact_at: NOW + 1 });
+			(Call::ApplyAuthorizedUpgrade { code }, Origin::None)
+		},
+	},
+];
+
+/// Reads the version of code with the real executor, but one `spec_version` ahead, so that the
+/// runtime's own code is accepted as an upgrade.
+#[derive(Clone)]
+struct NextVersion<E>(E);
+
+impl<E: ReadRuntimeVersion> ReadRuntimeVersion for NextVersion<E> {
+	fn read_runtime_version(
+		&self,
+		wasm_code: &[u8],
+		ext: &mut dyn Externalities,
+	) -> Result<Vec<u8>, String> {
+		let encoded = self.0.read_runtime_version(wasm_code, ext)?;
+		let mut version = RuntimeVersion::decode(&mut &encoded[..]).map_err(|e| e.to_string())?;
+		version.spec_version += 1;
+		Ok(version.encode())
+	}
+}
+
+/// The unsigned LEB128 encoding Wasm uses for lengths.
+fn leb128(mut n: usize) -> Vec<u8> {
+	let mut bytes = Vec::new();
+	loop {
+		let byte = (n & 0x7f) as u8;
+		n >>= 7;
+		if n == 0 {
+			bytes.push(byte);
+			return bytes
+		}
+		bytes.push(byte | 0x80);
+	}
+}
+
+/// `code` with a custom section of `extra` zeros appended, which hosts skip over.
+fn padded(code: &[u8], extra: usize) -> Vec<u8> {
+	let name = b"padding";
+	let mut section = leb128(name.len());
+	section.extend_from_slice(name);
+	section.resize(section.len() + extra, 0);
+
+	let mut padded = code.to_vec();
+	padded.push(0);
+	padded.extend(leb128(section.len()));
+	padded.extend(section);
+	padded
+}
+
+fn measure<E: ReadRuntimeVersion + Clone + 'static>(
+	name: &str,
+	setup: impl FnOnce() -> (Call, Origin),
+	executor: &E,
+) -> (u64, (u32, u32)) {
+	let mut ext = sp_io::TestExternalities::new_empty();
+	ext.register_extension(ReadRuntimeVersionExt::new(NextVersion(executor.clone())));
+	ext.execute_with(|| {
+		CurrentHeader::put(&Header::new(
+			NOW,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		));
+		let (call, origin) = setup();
+
+		tracking::take();
+		let start = Instant::now();
+		let result = dispatch(call, origin);
+		let nanos = start.elapsed().as_nanos() as u64;
+		let counts = tracking::take();
+
+		if let Err(e) = result {
+			panic!("benchmark {} failed to dispatch: {:?}", name, e);
+		}
+		(nanos, counts)
+	})
+}
+
+/// Measures `repeat` dispatches, returning the median time and the most reads and writes.
+fn measure_median(repeat: u32, mut time: impl FnMut() -> (u64, (u32, u32))) -> (u64, u32, u32) {
+	let mut times = Vec::new();
+	let (mut reads, mut writes) = (0, 0);
+	for _ in 0..repeat.max(1) {
+		let (nanos, counts) = time();
+		times.push(nanos);
+		reads = reads.max(counts.0);
+		writes = writes.max(counts.1);
+	}
+	times.sort_unstable();
+	(times[times.len() / 2], reads, writes)
+}
+
+/// Runs every benchmark `repeat` times. `code` is the runtime's uncompressed Wasm, which the calls
+/// taking runtime code are dispatched with, as it is and padded to twice its length. The host
+/// reads its version with `executor`.
+pub fn run<E: ReadRuntimeVersion + Clone + 'static>(
+	repeat: u32,
+	code: &[u8],
+	executor: E,
+) -> Vec<BenchmarkResult> {
+	let mut results: Vec<_> = BENCHMARKS
+		.iter()
+		.map(|benchmark| {
+			let (nanos, reads, writes) =
+				measure_median(repeat, || measure(benchmark.name, benchmark.setup, &executor));
+			BenchmarkResult { name: benchmark.name, nanos, reads, writes, nanos_per_kib: None }
+		})
+		.collect();
+
+	let long_code = padded(code, code.len());
+	for benchmark in CODE_BENCHMARKS {
+		let (short, reads, writes) = measure_median(repeat, || {
+			measure(benchmark.name, || (benchmark.setup)(code.to_vec()), &executor)
+		});
+		let (long, ..) = measure_median(repeat, || {
+			measure(benchmark.name, || (benchmark.setup)(long_code.clone()), &executor)
+		});
+		let extra_bytes = (long_code.len() - code.len()) as u64;
+		let nanos_per_kib = long.saturating_sub(short).saturating_mul(1024) / extra_bytes;
+		let nanos = short.saturating_sub(nanos_per_kib.saturating_mul(code.len() as u64) / 1024);
+		results.push(BenchmarkResult {
+			name: benchmark.name,
+			nanos,
+			reads,
+			writes,
+			nanos_per_kib: Some(nanos_per_kib),
+		});
+	}
+	results
+}
+
+/// `AuthorizeUpgrade` becomes `AUTHORIZE_UPGRADE`.
+fn constant_name(name: &str) -> String {
+	let mut constant = String::new();
+	for (i, c) in name.chars().enumerate() {
+		if c.is_uppercase() && i > 0 {
+			constant.push('_');
+		}
+		constant.push(c.to_ascii_uppercase());
+	}
+	constant
+}
+
+/// The contents of `weights/generated.rs` for `results`.
+pub fn weights_file(results: &[BenchmarkResult], repeat: u32) -> String {
+	let mut file = String::new();
+	let _ = writeln!(file, "//! Generated by `node-template benchmark`, do not edit by hand.");
+	let _ = writeln!(file, "//!");
+	let _ = writeln!(file, "//! The median time of {repeat} dispatches of each call.");
+	let _ = writeln!(file);
+	let _ = writeln!(file, "use super::Weight;");
+	for result in results {
+		let _ = writeln!(file);
+		let _ = writeln!(
+			file,
+			"pub const {}: Weight = Weight {{ nanos: {}, reads: {}, writes: {} }};",
+			constant_name(result.name),
+			result.nanos,
+			result.reads,
+			result.writes,
+		);
+		if let Some(nanos_per_kib) = result.nanos_per_kib {
+			let _ = writeln!(file);
+			let _ = writeln!(
+				file,
+				"pub const {}_PER_KIB: u64 = {};",
+				constant_name(result.name),
+				nanos_per_kib,
+			);
+		}
+	}
+	file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{upgrade::testing, VERSION};
+
+	#[test]
+	fn every_benchmark_dispatches() {
+		// Read by `EncodedVersion`, which ignores the padding.
+		let code = testing::code("frameless-runtime", VERSION.spec_version);
+		let results = run(1, &code, testing::EncodedVersion);
+		assert_eq!(results.len(), BENCHMARKS.len() + CODE_BENCHMARKS.len());
+		let add = results.iter().find(|r| r.name == "Add").unwrap();
+		assert_eq!((add.reads, add.writes), (1, 1));
+
+		let file = weights_file(&results, 1);
+		assert!(file.contains("pub const APPLY_AUTHORIZED_UPGRADE: Weight = Weight { nanos: "));
+		assert!(file.contains("pub const APPLY_AUTHORIZED_UPGRADE_PER_KIB: u64 = "));
+	}
+}
@@ -0,0 +1,104 @@
+//! Aura and Grandpa authorities.
+//!
+//! The authority sets live in storage so that admins can change them. Every change is announced
+//! to the node through consensus digests, which Grandpa in particular relies on to follow the set.
+
+use crate::{deposit_log, storage::StorageValue, BlockNumber, EMOJI};
+use log::info;
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_consensus_aura::{sr25519::AuthorityId as AuraId, AURA_ENGINE_ID};
+use sp_finality_grandpa::{AuthorityId as GrandpaId, AuthorityList, SetId, GRANDPA_ENGINE_ID};
+use sp_runtime::generic::DigestItem;
+use sp_std::prelude::*;
+
+storage_value! {
+	/// The accounts allowed to author blocks, in slot order.
+	pub struct AuraAuthorities(b"Aura", b"Authorities"): Vec<AuraId>;
+}
+
+storage_value! {
+	/// The current Grandpa voters and their weights.
+	pub struct GrandpaAuthorities(b"Grandpa", b"Authorities"): AuthorityList;
+}
+
+storage_value! {
+	/// Incremented on every change of [`GrandpaAuthorities`].
+	pub struct GrandpaSetId(b"Grandpa", b"CurrentSetId"): SetId;
+}
+
+/// Why a new authority set was refused.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum AuthorityError {
+	/// Without Aura authorities no block can be authored, without Grandpa ones none finalized.
+	NoAuthorities,
+}
+
+/// Checks that both sets can keep the chain going.
+pub fn check_authorities<A, G>(aura: &[A], grandpa: &[G]) -> Result<(), AuthorityError> {
+	if aura.is_empty() || grandpa.is_empty() {
+		return Err(AuthorityError::NoAuthorities)
+	}
+	Ok(())
+}
+
+/// Every Grandpa voter gets the same weight.
+pub fn grandpa_weighted(authorities: Vec<GrandpaId>) -> AuthorityList {
+	authorities.into_iter().map(|id| (id, 1)).collect()
+}
+
+/// Replaces both authority sets, effective from the next block.
+pub fn set_authorities(aura: Vec<AuraId>, grandpa: Vec<GrandpaId>) -> Result<(), AuthorityError> {
+	check_authorities(&aura, &grandpa)?;
+	info!(
+		target: "consensus",
+		"{EMOJI} new authorities: {} aura, {} grandpa", aura.len(), grandpa.len()
+	);
+	let grandpa = grandpa_weighted(grandpa);
+
+	deposit_log(DigestItem::Consensus(
+		AURA_ENGINE_ID,
+		sp_consensus_aura::ConsensusLog::AuthoritiesChange(aura.clone()).encode(),
+	));
+	deposit_log(DigestItem::Consensus(
+		GRANDPA_ENGINE_ID,
+		sp_finality_grandpa::ConsensusLog::<BlockNumber>::ScheduledChange(
+			sp_finality_grandpa::ScheduledChange { next_authorities: grandpa.clone(), delay: 0 },
+		)
+		.encode(),
+	));
+
+	AuraAuthorities::put(&aura);
+	GrandpaAuthorities::put(&grandpa);
+	GrandpaSetId::mutate(|id| *id = Some(id.unwrap_or(0) + 1));
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{ed25519, sr25519};
+
+	#[test]
+	fn authority_sets_must_not_be_empty() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let aura: AuraId = sr25519::Public::from_raw([1; 32]).into();
+			let grandpa: GrandpaId = ed25519::Public::from_raw([2; 32]).into();
+
+			assert_eq!(
+				set_authorities(vec![], vec![grandpa.clone()]),
+				Err(AuthorityError::NoAuthorities)
+			);
+			assert_eq!(
+				set_authorities(vec![aura.clone()], vec![]),
+				Err(AuthorityError::NoAuthorities)
+			);
+			assert_eq!(AuraAuthorities::get(), None);
+			assert_eq!(GrandpaSetId::get(), None);
+
+			assert_eq!(set_authorities(vec![aura.clone()], vec![grandpa]), Ok(()));
+			assert_eq!(AuraAuthorities::get(), Some(vec![aura]));
+			assert_eq!(GrandpaSetId::get(), Some(1));
+		});
+	}
+}
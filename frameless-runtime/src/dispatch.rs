@@ -0,0 +1,344 @@
+//! Dispatching calls on behalf of an origin.
+
+use crate::{
+	admin::{self, AdminError},
+	block_number,
+	consensus::{self, AuthorityError},
+	events::{deposit_event, Event},
+	multisig::{self, MultisigError},
+	offchain, rewards,
+	scheduler::{self, SchedulerError},
+	storage::{with_transaction, StorageValue},
+	upgrade::{self, CodeError},
+	Address, AsCompact, Bit, Call, Value, EMOJI, KILL_PASSWORD, UPGRADE_PASSWORD,
+};
+use log::info;
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_runtime::{ArithmeticError, ModuleError, MAX_MODULE_ERROR_ENCODED_SIZE};
+use sp_std::prelude::*;
+
+/// The index of the `Flipper` pallet in the metadata, whose errors are [`DispatchError`]s.
+pub const FLIPPER_PALLET_INDEX: u8 = 1;
+
+/// On whose behalf a call is dispatched.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum Origin {
+	/// The verified signer of an extrinsic.
+	Signed(Address),
+	/// A proposal approved by enough admins, see [`multisig`].
+	Admin,
+	/// An unsigned extrinsic, see [`crate::extrinsic::validate_unsigned`].
+	None,
+}
+
+/// Why a call failed.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum DispatchError {
+	/// The origin may not dispatch the call.
+	BadOrigin,
+	/// The call needs a password, and it was wrong.
+	BadPassword,
+	/// The call is not allowed in the current state, e.g. killing an unpaused chain.
+	CallFiltered,
+	/// The result of an arithmetic operation does not fit its type.
+	Overflow,
+	Admin(AdminError),
+	Multisig(MultisigError),
+	Scheduler(SchedulerError),
+	Upgrade(CodeError),
+	Consensus(AuthorityError),
+}
+
+impl From<AdminError> for DispatchError {
+	fn from(e: AdminError) -> Self {
+		DispatchError::Admin(e)
+	}
+}
+
+impl From<AuthorityError> for DispatchError {
+	fn from(e: AuthorityError) -> Self {
+		DispatchError::Consensus(e)
+	}
+}
+
+impl From<MultisigError> for DispatchError {
+	fn from(e: MultisigError) -> Self {
+		DispatchError::Multisig(e)
+	}
+}
+
+impl From<SchedulerError> for DispatchError {
+	fn from(e: SchedulerError) -> Self {
+		DispatchError::Scheduler(e)
+	}
+}
+
+impl From<CodeError> for DispatchError {
+	fn from(e: CodeError) -> Self {
+		DispatchError::Upgrade(e)
+	}
+}
+
+pub type DispatchResult = Result<(), DispatchError>;
+
+/// Reports our errors the way FRAME chains do, so that clients can tell what failed: errors
+/// without a generic counterpart become errors of the `Flipper` pallet, encoded in the first bytes
+/// of the module error.
+impl From<DispatchError> for sp_runtime::DispatchError {
+	fn from(e: DispatchError) -> Self {
+		match e {
+			DispatchError::BadOrigin => sp_runtime::DispatchError::BadOrigin,
+			DispatchError::Overflow => ArithmeticError::Overflow.into(),
+			e => {
+				let mut error = [0; MAX_MODULE_ERROR_ENCODED_SIZE];
+				e.using_encoded(|bytes| error[..bytes.len()].copy_from_slice(bytes));
+				ModuleError { index: FLIPPER_PALLET_INDEX, error, message: None }.into()
+			},
+		}
+	}
+}
+
+/// The error behind an `apply_extrinsic` failure, if it is one of ours.
+pub fn decode_error(error: sp_runtime::DispatchError) -> Option<DispatchError> {
+	match error {
+		sp_runtime::DispatchError::BadOrigin => Some(DispatchError::BadOrigin),
+		sp_runtime::DispatchError::Arithmetic(ArithmeticError::Overflow) =>
+			Some(DispatchError::Overflow),
+		sp_runtime::DispatchError::Module(ModuleError { index, error, .. })
+			if index == FLIPPER_PALLET_INDEX =>
+			DispatchError::decode(&mut &error[..]).ok(),
+		_ => None,
+	}
+}
+
+impl Call {
+	/// Whether the call may only be dispatched with [`Origin::Admin`].
+	pub fn is_admin_call(&self) -> bool {
+		matches!(
+			self,
+			Call::Upgrade { .. } |
+				Call::Kill { .. } |
+				Call::AuthorizeUpgrade { .. } |
+				Call::Pause | Call::Resume |
+				Call::SetAdmins { .. } |
+				Call::SetAuthorities { .. } |
+				Call::SetBlockReward { .. }
+		)
+	}
+}
+
+fn ensure_signed(origin: Origin) -> Result<Address, DispatchError> {
+	match origin {
+		Origin::Signed(who) => Ok(who),
+		Origin::Admin | Origin::None => Err(DispatchError::BadOrigin),
+	}
+}
+
+pub fn dispatch(call: Call, origin: Origin) -> DispatchResult {
+	// Admin calls need a multisig proposal. The exception is `Pause`: any single admin can pull
+	// the emergency brake.
+	if call.is_admin_call() && origin != Origin::Admin {
+		let is_single_admin_pause = match &origin {
+			Origin::Signed(who) => call == Call::Pause && admin::is_admin(who),
+			Origin::Admin | Origin::None => false,
+		};
+		if !is_single_admin_pause {
+			info!(target: "admin", "{EMOJI} {:?} denied: needs an approved proposal", call);
+			return Err(DispatchError::BadOrigin)
+		}
+	}
+
+	match call {
+		Call::Flip => {
+			let mut bit = Bit::get().unwrap_or(false);
+			info!(target: "flipper", "{EMOJI} current bit: {bit}");
+			bit = !bit;
+			Bit::put(&bit);
+			info!(target: "flipper", "{EMOJI} stored flipped bit: {bit}");
+		},
+		Call::Add(value) => {
+			let existing = Value::get().unwrap_or(0);
+			info!(target: "adder", "{EMOJI} existing value: {existing} supplied value: {}", value.0);
+			let result = existing.checked_add(value.0).ok_or(DispatchError::Overflow)?;
+			Value::put(&result);
+			info!(target: "adder", "{EMOJI} stored result: {result}");
+		},
+		Call::Multiply(value) => {
+			let existing = Value::get().unwrap_or(1);
+			info!(
+				target: "multiplier",
+				"{EMOJI} existing value: {existing} supplied value: {}", value.0
+			);
+			let result = existing.checked_mul(value.0).ok_or(DispatchError::Overflow)?;
+			Value::put(&result);
+			info!(target: "multiplier", "{EMOJI} stored result: {result}");
+		},
+		Call::Upgrade { password, payload } => {
+			if password != UPGRADE_PASSWORD {
+				return Err(DispatchError::BadPassword)
+			}
+			info!(target: "upgrader", "{EMOJI} upgrade initiated");
+			upgrade::set_code(&payload)?;
+		},
+		Call::Kill { password } => {
+			if password != KILL_PASSWORD {
+				return Err(DispatchError::BadPassword)
+			}
+			if !admin::is_paused() {
+				return Err(DispatchError::CallFiltered)
+			}
+			info!(target: "killer", "{EMOJI} kill switch engaged");
+			crate::storage::tracking::note_write();
+			sp_io::storage::set(sp_storage::well_known_keys::CODE, &[]);
+		},
+		Call::AuthorizeUpgrade { code_hash, enact_at } =>
+			upgrade::authorize_upgrade(code_hash, enact_at, block_number())?,
+		Call::ApplyAuthorizedUpgrade { code } => upgrade::apply_authorized_upgrade(code)?,
+		Call::Pause => admin::pause(),
+		Call::Resume => admin::resume(),
+		Call::SetAdmins { admins, threshold } => admin::set_admins(admins, threshold)?,
+		Call::SetAuthorities { aura, grandpa } => consensus::set_authorities(
+			aura.into_iter()
+				.map(|k| sp_core::sr25519::Public::from_raw(k.0).into())
+				.collect(),
+			grandpa
+				.into_iter()
+				.map(|k| sp_core::ed25519::Public::from_raw(k.0).into())
+				.collect(),
+		)?,
+		Call::Propose { call } => {
+			multisig::propose(ensure_signed(origin)?, *call)?;
+		},
+		Call::Approve { id } => multisig::approve(ensure_signed(origin)?, id)?,
+		Call::Batch(calls) => batch(calls, origin),
+		Call::BatchAll(calls) => batch_all(calls, origin)?,
+		Call::Schedule { when, call } => {
+			scheduler::schedule(origin, when, *call, block_number())?;
+		},
+		Call::CancelScheduled { id } => scheduler::cancel(origin, id)?,
+		Call::SetBlockReward { reward } => rewards::set_block_reward(reward),
+		Call::Heartbeat { heartbeat, .. } => {
+			// Only `validate_unsigned` checks the authority's signature.
+			if origin != Origin::None {
+				return Err(DispatchError::BadOrigin)
+			}
+			offchain::note_heartbeat(&heartbeat);
+			return dispatch(Call::Add(AsCompact(1)), origin)
+		},
+	}
+	Ok(())
+}
+
+/// Dispatches every call, each in its own storage transaction, reporting each outcome as an
+/// event. Failing calls do not stop the batch.
+fn batch(calls: Vec<Call>, origin: Origin) {
+	for (index, call) in calls.into_iter().enumerate() {
+		let index = index as u32;
+		match with_transaction(|| dispatch(call, origin.clone())) {
+			Ok(()) => deposit_event(Event::BatchItemCompleted { index }),
+			Err(error) => {
+				info!(target: "batch", "{EMOJI} batch item {index} failed: {:?}", error);
+				deposit_event(Event::BatchItemFailed { index, error })
+			},
+		}
+	}
+	deposit_event(Event::BatchCompleted);
+}
+
+/// Dispatches every call in a single storage transaction: either all succeed, or none has any
+/// effect and the first error is returned.
+fn batch_all(calls: Vec<Call>, origin: Origin) -> DispatchResult {
+	with_transaction(|| {
+		for (index, call) in calls.into_iter().enumerate() {
+			dispatch(call, origin.clone()).map_err(|error| {
+				info!(target: "batch", "{EMOJI} batch_all item {index} failed: {:?}", error);
+				error
+			})?;
+		}
+		Ok(())
+	})?;
+	deposit_event(Event::BatchCompleted);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::events::{EventRecord, Events};
+	use sp_core::H256;
+
+	fn signed() -> Origin {
+		Origin::Signed(H256::repeat_byte(1))
+	}
+
+	fn events() -> Vec<Event> {
+		Events::get()
+			.unwrap_or_default()
+			.into_iter()
+			.map(EventRecord::into_event)
+			.collect()
+	}
+
+	#[test]
+	fn batch_reports_each_call() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let calls = vec![Call::Add(AsCompact(2)), Call::Resume, Call::Multiply(AsCompact(3))];
+
+			assert_eq!(dispatch(Call::Batch(calls), signed()), Ok(()));
+
+			assert_eq!(Value::get(), Some(6));
+			assert_eq!(
+				events(),
+				vec![
+					Event::BatchItemCompleted { index: 0 },
+					Event::BatchItemFailed { index: 1, error: DispatchError::BadOrigin },
+					Event::BatchItemCompleted { index: 2 },
+					Event::BatchCompleted,
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn reports_errors_as_runtime_errors() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			Value::put(&u32::MAX);
+			assert_eq!(dispatch(Call::Add(AsCompact(1)), signed()), Err(DispatchError::Overflow));
+			assert_eq!(Value::get(), Some(u32::MAX));
+		});
+
+		for error in [
+			DispatchError::BadOrigin,
+			DispatchError::Overflow,
+			DispatchError::BadPassword,
+			DispatchError::Upgrade(CodeError::SpecVersionNotIncreased),
+		] {
+			assert!(error.encoded_size() <= MAX_MODULE_ERROR_ENCODED_SIZE);
+			assert_eq!(decode_error(error.clone().into()), Some(error));
+		}
+		assert_eq!(
+			decode_error(sp_runtime::DispatchError::BadOrigin),
+			Some(DispatchError::BadOrigin)
+		);
+		assert_eq!(decode_error(sp_runtime::DispatchError::CannotLookup), None);
+	}
+
+	#[test]
+	fn batch_all_is_atomic() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let calls = vec![Call::Flip, Call::Add(AsCompact(2)), Call::Resume];
+
+			assert_eq!(dispatch(Call::BatchAll(calls), signed()), Err(DispatchError::BadOrigin));
+
+			assert_eq!(Bit::get(), None);
+			assert_eq!(Value::get(), None);
+			assert!(events().is_empty());
+
+			let calls = vec![Call::Add(AsCompact(2)), Call::Multiply(AsCompact(3))];
+			assert_eq!(dispatch(Call::BatchAll(calls), signed()), Ok(()));
+			assert_eq!(Value::get(), Some(6));
+			assert_eq!(events(), vec![Event::BatchCompleted]);
+		});
+	}
+}
@@ -0,0 +1,94 @@
+//! Events, kept in state for the duration of a block so that clients can read what happened.
+//!
+//! They are stored like the events of FRAME chains: prefixed with the index of their pallet, see
+//! [`RuntimeEvent`], in records along with the phase of the block which deposited them.
+
+use crate::{
+	dispatch::DispatchError, multisig::ProposalId, scheduler::ScheduledId, storage::StorageValue,
+	Address, Balance, BlockNumber, CurrentHeader, ExtrinsicIndex,
+};
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_core::H256;
+use sp_std::prelude::*;
+
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum Event {
+	/// A call of a `Call::Batch` succeeded.
+	BatchItemCompleted { index: u32 },
+	/// A call of a `Call::Batch` failed, and its changes were discarded.
+	BatchItemFailed { index: u32, error: DispatchError },
+	/// All calls of a `Call::Batch` or `Call::BatchAll` were dispatched.
+	BatchCompleted,
+	/// A multisig proposal reached its threshold and was dispatched.
+	ProposalExecuted { id: ProposalId, result: Result<(), DispatchError> },
+	/// A call was scheduled for block `when`.
+	Scheduled { id: ScheduledId, when: BlockNumber },
+	/// A scheduled call was cancelled.
+	ScheduleCancelled { id: ScheduledId },
+	/// A scheduled call was dispatched.
+	ScheduledDispatched { id: ScheduledId, result: Result<(), DispatchError> },
+	/// An extrinsic failed. Its fee and nonce were still taken.
+	ExtrinsicFailed { error: DispatchError },
+	/// The author of the block was paid the block reward.
+	AuthorRewarded { who: Address, amount: Balance },
+}
+
+/// An event prefixed with the index of the pallet it belongs to, which is always
+/// [`crate::dispatch::FLIPPER_PALLET_INDEX`].
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum RuntimeEvent {
+	#[codec(index = 1)]
+	Flipper(Event),
+}
+
+/// The part of the block an event was deposited in, like `frame_system::Phase`.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum Phase {
+	/// While applying the extrinsic with this index.
+	ApplyExtrinsic(u32),
+	/// In `finalize_block`.
+	Finalization,
+	/// In `initialize_block`, e.g. by scheduled calls.
+	Initialization,
+}
+
+/// An event along with when it happened, like `frame_system::EventRecord`.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub struct EventRecord {
+	pub phase: Phase,
+	pub event: RuntimeEvent,
+	/// Always empty, we do not index events by topic.
+	pub topics: Vec<H256>,
+}
+
+impl EventRecord {
+	pub fn into_event(self) -> Event {
+		match self.event {
+			RuntimeEvent::Flipper(event) => event,
+		}
+	}
+}
+
+storage_value! {
+	/// The events of the current block, cleared at the start of the next one.
+	pub struct Events(b"System", b"Events"): Vec<EventRecord>;
+}
+
+pub fn deposit_event(event: Event) {
+	let phase = match ExtrinsicIndex::get() {
+		Some(index) => Phase::ApplyExtrinsic(index),
+		// `finalize_block` takes the header first thing.
+		None if CurrentHeader::exists() => Phase::Initialization,
+		None => Phase::Finalization,
+	};
+	let record = EventRecord { phase, event: RuntimeEvent::Flipper(event), topics: Vec::new() };
+	// Appending avoids decoding all events of the block for every new one.
+	crate::storage::tracking::note_write();
+	sp_io::storage::append(&Events::hashed_key(), record.encode());
+}
+
+/// Called from `initialize_block`.
+pub fn reset_events() {
+	Events::kill();
+}
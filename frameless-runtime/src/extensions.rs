@@ -0,0 +1,166 @@
+//! The checks signed extrinsics carry alongside their call.
+//!
+//! These mirror the signed extensions of FRAME chains, so that standard tooling can sign for us:
+//! the era bounds the lifetime of an extrinsic, the nonce orders the extrinsics of an account and
+//! prevents replays, and the tip buys priority in the transaction pool. On top of [`SignedExtra`],
+//! the signature covers the data returned by [`additional_signed`], which the chain already knows
+//! and so the extrinsic does not carry.
+//!
+//! The genesis hash is recorded by block 1. Chains which were past it before `spec_version` 5 never
+//! recorded it, and get it from the [`crate::migrations::v4`] migration instead.
+
+use crate::{
+	balances::{self, Balance},
+	storage::StorageMap,
+	Address, BlockNumber, VERSION,
+};
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_core::H256;
+use sp_runtime::{
+	generic::Era,
+	transaction_validity::{
+		InvalidTransaction, TransactionValidity, TransactionValidityError, ValidTransaction,
+	},
+	SaturatedConversion,
+};
+use sp_std::prelude::*;
+
+/// How many recent block hashes are kept for checking eras.
+pub const BLOCK_HASH_COUNT: BlockNumber = 256;
+
+pub type Nonce = u32;
+
+storage_map! {
+	/// The hashes of the genesis block and of the last [`BLOCK_HASH_COUNT`] blocks.
+	pub struct BlockHash(b"System", b"BlockHash"): map BlockNumber => H256;
+}
+
+storage_map! {
+	/// The nonce the next extrinsic of an account has to carry.
+	pub struct Nonces(b"System", b"Nonces"): map Address => Nonce;
+}
+
+/// The checks a signed extrinsic carries, in the order of the FRAME signed extensions
+/// `CheckMortality`, `CheckNonce` and `ChargeTransactionPayment`.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub struct SignedExtra {
+	pub era: Era,
+	#[codec(compact)]
+	pub nonce: Nonce,
+	#[codec(compact)]
+	pub tip: Balance,
+}
+
+/// Spec version, transaction version, genesis hash and the hash of the block the era starts at,
+/// as `CheckSpecVersion`, `CheckTxVersion`, `CheckGenesis` and `CheckMortality` sign them.
+pub fn additional_signed(
+	extra: &SignedExtra,
+	now: BlockNumber,
+) -> Result<(u32, u32, H256, H256), TransactionValidityError> {
+	let birth = extra.era.birth(now.into()).saturated_into::<BlockNumber>();
+	let genesis = BlockHash::get(&0).ok_or(InvalidTransaction::AncientBirthBlock)?;
+	let checkpoint = BlockHash::get(&birth).ok_or(InvalidTransaction::AncientBirthBlock)?;
+	Ok((VERSION.spec_version, VERSION.transaction_version, genesis, checkpoint))
+}
+
+/// Checks `extra` for the transaction pool, where extrinsics may arrive ahead of their nonce.
+pub fn validate(who: &Address, extra: &SignedExtra, now: BlockNumber) -> TransactionValidity {
+	let nonce = Nonces::get(who).unwrap_or(0);
+	if extra.nonce < nonce {
+		return Err(InvalidTransaction::Stale.into())
+	}
+	if balances::free_balance(who) < extra.tip {
+		return Err(InvalidTransaction::Payment.into())
+	}
+
+	let requires =
+		if extra.nonce > nonce { vec![(who, extra.nonce - 1).encode()] } else { Vec::new() };
+	Ok(ValidTransaction {
+		priority: extra.tip.saturated_into(),
+		requires,
+		provides: vec![(who, extra.nonce).encode()],
+		longevity: extra.era.death(now.into()).saturating_sub(now.into()),
+		propagate: true,
+	})
+}
+
+/// Checks `extra` before dispatch, bumping the nonce and taking the tip. Tips are burned.
+pub fn pre_dispatch(who: &Address, extra: &SignedExtra) -> Result<(), TransactionValidityError> {
+	let nonce = Nonces::get(who).unwrap_or(0);
+	if extra.nonce != nonce {
+		return Err(if extra.nonce < nonce {
+			InvalidTransaction::Stale
+		} else {
+			InvalidTransaction::Future
+		}
+		.into())
+	}
+	balances::withdraw(who, extra.tip).map_err(|_| InvalidTransaction::Payment)?;
+	Nonces::insert(who, &(nonce + 1));
+	Ok(())
+}
+
+/// Records the hash of block `number - 1`, forgetting the ones eras can no longer refer to.
+/// Called from `initialize_block`, and before validating transactions for block `number`.
+pub fn note_parent_hash(number: BlockNumber, parent_hash: H256) {
+	if number == 0 {
+		return
+	}
+	BlockHash::insert(&(number - 1), &parent_hash);
+	// The genesis hash is needed for every extrinsic.
+	match (number - 1).checked_sub(BLOCK_HASH_COUNT) {
+		Some(0) | None => (),
+		Some(old) => BlockHash::remove(&old),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::balances::Balances;
+
+	fn extra(nonce: Nonce, tip: Balance) -> SignedExtra {
+		SignedExtra { era: Era::Immortal, nonce, tip }
+	}
+
+	#[test]
+	fn checks_nonce_and_tip() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let alice = H256::repeat_byte(1);
+			Balances::insert(&alice, &10);
+			Nonces::insert(&alice, &1);
+
+			assert_eq!(validate(&alice, &extra(0, 0), 1), Err(InvalidTransaction::Stale.into()));
+			assert_eq!(validate(&alice, &extra(1, 11), 1), Err(InvalidTransaction::Payment.into()));
+			let future = validate(&alice, &extra(2, 5), 1).unwrap();
+			assert_eq!(future.requires, vec![(alice, 1u32).encode()]);
+			assert_eq!(future.priority, 5);
+
+			assert_eq!(pre_dispatch(&alice, &extra(2, 5)), Err(InvalidTransaction::Future.into()));
+			assert_eq!(pre_dispatch(&alice, &extra(1, 5)), Ok(()));
+			assert_eq!(Nonces::get(&alice), Some(2));
+			assert_eq!(balances::free_balance(&alice), 5);
+		});
+	}
+
+	#[test]
+	fn keeps_recent_and_genesis_hashes() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			for number in 1..=BLOCK_HASH_COUNT + 2 {
+				note_parent_hash(number, H256::repeat_byte(number as u8));
+			}
+			assert!(BlockHash::contains_key(&0));
+			assert!(!BlockHash::contains_key(&1));
+			assert!(BlockHash::contains_key(&2));
+
+			let mortal = SignedExtra { era: Era::mortal(64, 200), nonce: 0, tip: 0 };
+			let (_, _, genesis, checkpoint) = additional_signed(&mortal, 200).unwrap();
+			assert_eq!(genesis, H256::repeat_byte(1));
+			assert_eq!(
+				checkpoint,
+				BlockHash::get(&mortal.era.birth(200).saturated_into()).unwrap()
+			);
+		});
+	}
+}
@@ -0,0 +1,418 @@
+//! The extrinsic format.
+//!
+//! Extrinsics are encoded like `sp_runtime::generic::UncheckedExtrinsic`, so that standard tooling
+//! can build them: a compact length prefix, a version byte, then for signed extrinsics the signer,
+//! the signature and the [`SignedExtra`], and finally the call. The top bit of the version byte
+//! tells signed extrinsics apart from unsigned ones, which are either inherents put in by the block
+//! author or transactions which carry no signer, such as uploads of authorized code. Unsigned calls
+//! are only valid if [`validate_unsigned`] says so. As on FRAME chains, the call is prefixed with
+//! the index of its pallet, see [`RuntimeCall`].
+
+use crate::{
+	dispatch::{Origin, FLIPPER_PALLET_INDEX},
+	extensions::{self, SignedExtra},
+	offchain,
+	storage::StorageValue,
+	upgrade, Address, BlockNumber, Call, Signature,
+};
+use parity_scale_codec::{Compact, Decode, DecodeLimit, Encode, Error, Input};
+use scale_info::{build::Fields, meta_type, Path, Type, TypeInfo, TypeParameter};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_core::H256;
+use sp_runtime::{
+	traits::{Extrinsic, Verify},
+	transaction_validity::{
+		InvalidTransaction, TransactionValidity, TransactionValidityError, UnknownTransaction,
+		ValidTransaction,
+	},
+	AccountId32, MultiAddress,
+};
+use sp_std::prelude::*;
+
+/// The version of the format, in the low bits of the version byte.
+pub const EXTRINSIC_FORMAT_VERSION: u8 = 4;
+/// How deeply calls may nest in batches, proposals and schedules, so decoding cannot overflow the
+/// stack.
+pub const MAX_EXTRINSIC_DEPTH: u32 = 256;
+/// Set in the version byte of signed extrinsics.
+const SIGNED_BIT: u8 = 0b1000_0000;
+
+/// How a signer refers to their account. We have no account indices.
+pub type Lookup = MultiAddress<Address, ()>;
+
+/// The signer, their signature of the signing payload, and the checks they signed.
+pub type SignaturePayload = (Lookup, Signature, SignedExtra);
+
+/// A call as it is encoded in extrinsics: prefixed with the index of the pallet it belongs to,
+/// which is always [`FLIPPER_PALLET_INDEX`].
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum RuntimeCall {
+	#[codec(index = 1)]
+	Flipper(Call),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BasicExtrinsic {
+	/// `None` for inherents and unsigned transactions.
+	pub signature: Option<SignaturePayload>,
+	pub call: Call,
+}
+
+/// An extrinsic whose signature has been verified.
+pub struct CheckedExtrinsic {
+	/// The signer and the checks still to be applied, for signed extrinsics.
+	pub signed: Option<(Address, SignedExtra)>,
+	pub call: Call,
+}
+
+impl BasicExtrinsic {
+	pub fn new_signed(call: Call, signature: SignaturePayload) -> Self {
+		Self { signature: Some(signature), call }
+	}
+
+	pub fn new_unsigned(call: Call) -> Self {
+		Self { signature: None, call }
+	}
+
+	/// Verifies the signature, if any, as of block `now`.
+	pub fn check(self, now: BlockNumber) -> Result<CheckedExtrinsic, TransactionValidityError> {
+		self.check_with(now, true)
+	}
+
+	/// Like [`Self::check`], but only trusts the signer's address without `signature_check`.
+	/// Only for re-executing past blocks, whose signatures cover the `spec_version` they were
+	/// signed for rather than the current one.
+	pub fn check_with(
+		self,
+		now: BlockNumber,
+		signature_check: bool,
+	) -> Result<CheckedExtrinsic, TransactionValidityError> {
+		let (lookup, signature, extra) = match self.signature {
+			Some(signature) => signature,
+			None => return Ok(CheckedExtrinsic { signed: None, call: self.call }),
+		};
+		let who = lookup_address(lookup)?;
+		if signature_check {
+			let payload = signing_payload(&self.call, &extra, now)?;
+			// Also recovers ecdsa keys and compares their hash with the address.
+			if !signature.verify(&payload[..], &AccountId32::new(who.0)) {
+				return Err(InvalidTransaction::BadProof.into())
+			}
+		}
+		Ok(CheckedExtrinsic { signed: Some((who, extra)), call: self.call })
+	}
+}
+
+impl CheckedExtrinsic {
+	/// The origin the call is dispatched with.
+	pub fn origin(&self) -> Origin {
+		match &self.signed {
+			Some((who, _)) => Origin::Signed(*who),
+			None => Origin::None,
+		}
+	}
+}
+
+fn lookup_address(lookup: Lookup) -> Result<Address, TransactionValidityError> {
+	match lookup {
+		MultiAddress::Id(who) => Ok(who),
+		MultiAddress::Address32(who) => Ok(H256(who)),
+		_ => Err(UnknownTransaction::CannotLookup.into()),
+	}
+}
+
+/// What the signer signs, like `sp_runtime::generic::SignedPayload`: long payloads are hashed.
+pub fn signing_payload(
+	call: &Call,
+	extra: &SignedExtra,
+	now: BlockNumber,
+) -> Result<Vec<u8>, TransactionValidityError> {
+	// `RuntimeCall::Flipper(call)`, without cloning the call.
+	let payload =
+		(FLIPPER_PALLET_INDEX, call, extra, extensions::additional_signed(extra, now)?).encode();
+	if payload.len() > 256 {
+		return Ok(sp_io::hashing::blake2_256(&payload).to_vec())
+	}
+	Ok(payload)
+}
+
+impl Encode for BasicExtrinsic {
+	fn encode(&self) -> Vec<u8> {
+		let mut inner = Vec::new();
+		match &self.signature {
+			Some(signature) => {
+				inner.push(EXTRINSIC_FORMAT_VERSION | SIGNED_BIT);
+				signature.encode_to(&mut inner);
+			},
+			None => inner.push(EXTRINSIC_FORMAT_VERSION),
+		}
+		// `RuntimeCall::Flipper(call)`, without cloning the call.
+		inner.push(FLIPPER_PALLET_INDEX);
+		self.call.encode_to(&mut inner);
+		// The length prefix lets clients skip extrinsics they cannot decode.
+		inner.encode()
+	}
+}
+
+impl Decode for BasicExtrinsic {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+		let _length: Compact<u32> = Decode::decode(input)?;
+		let version = input.read_byte()?;
+		if version & !SIGNED_BIT != EXTRINSIC_FORMAT_VERSION {
+			return Err("Unsupported extrinsic format version".into())
+		}
+		let signature =
+			if version & SIGNED_BIT != 0 { Some(SignaturePayload::decode(input)?) } else { None };
+		let RuntimeCall::Flipper(call) =
+			RuntimeCall::decode_with_depth_limit(MAX_EXTRINSIC_DEPTH, input)?;
+		Ok(Self { signature, call })
+	}
+}
+
+// Clients expect extrinsics as hex encoded bytes, as for `UncheckedExtrinsic`.
+#[cfg(feature = "std")]
+impl Serialize for BasicExtrinsic {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.using_encoded(|bytes| sp_core::bytes::serialize(bytes, serializer))
+	}
+}
+
+#[cfg(feature = "std")]
+impl<'a> Deserialize<'a> for BasicExtrinsic {
+	fn deserialize<D: serde::Deserializer<'a>>(deserializer: D) -> Result<Self, D::Error> {
+		let bytes = sp_core::bytes::deserialize(deserializer)?;
+		Decode::decode(&mut &bytes[..])
+			.map_err(|e| serde::de::Error::custom(format!("Decode error: {}", e)))
+	}
+}
+
+#[cfg(feature = "std")]
+impl parity_util_mem::MallocSizeOf for BasicExtrinsic {
+	fn size_of(&self, ops: &mut parity_util_mem::MallocSizeOfOps) -> usize {
+		// Signatures live on the stack.
+		self.call.size_of(ops)
+	}
+}
+
+// Described like `UncheckedExtrinsic`, whose type parameters clients read the parts from.
+impl TypeInfo for BasicExtrinsic {
+	type Identity = BasicExtrinsic;
+
+	fn type_info() -> Type {
+		Type::builder()
+			.path(Path::new("UncheckedExtrinsic", module_path!()))
+			.type_params(vec![
+				TypeParameter::new("Address", Some(meta_type::<Lookup>())),
+				TypeParameter::new("Call", Some(meta_type::<RuntimeCall>())),
+				TypeParameter::new("Signature", Some(meta_type::<Signature>())),
+				TypeParameter::new("Extra", Some(meta_type::<SignedExtra>())),
+			])
+			.composite(Fields::unnamed().field(|f| f.ty::<Vec<u8>>()))
+	}
+}
+
+impl Extrinsic for BasicExtrinsic {
+	type Call = Call;
+	type SignaturePayload = SignaturePayload;
+
+	fn is_signed(&self) -> Option<bool> {
+		Some(self.signature.is_some())
+	}
+
+	fn new(call: Self::Call, signature: Option<Self::SignaturePayload>) -> Option<Self> {
+		Some(Self { signature, call })
+	}
+}
+
+/// Whether `call` may be submitted without a signature. Unsigned transactions pay nothing and
+/// cannot be traced back to anyone, so each call allowed here has to make spamming pointless.
+pub fn validate_unsigned(call: &Call, now: BlockNumber) -> TransactionValidity {
+	match call {
+		// Only the blob matching the authorized hash is accepted, and only once.
+		Call::ApplyAuthorizedUpgrade { code } => {
+			let code_hash = H256(sp_io::hashing::blake2_256(code));
+			match upgrade::Authorized::get() {
+				Some(authorized) if authorized.code_hash == code_hash => (),
+				_ => return Err(InvalidTransaction::Call.into()),
+			}
+			if upgrade::PendingCode::exists() {
+				return Err(InvalidTransaction::Stale.into())
+			}
+			Ok(ValidTransaction {
+				provides: vec![(b"upgrade", code_hash).encode()],
+				..Default::default()
+			})
+		},
+		// Signed by an authority, and only one per block.
+		Call::Heartbeat { heartbeat, signature } =>
+			offchain::validate_heartbeat(heartbeat, signature, now),
+		_ => Err(UnknownTransaction::NoUnsignedValidator.into()),
+	}
+}
+
+/// Signs `call` for block `now` with an immortal era and no tip.
+#[cfg(test)]
+pub(crate) fn sign(
+	pair: &sp_core::sr25519::Pair,
+	call: Call,
+	nonce: extensions::Nonce,
+	now: BlockNumber,
+) -> BasicExtrinsic {
+	use sp_core::Pair;
+	let extra = SignedExtra { era: sp_runtime::generic::Era::Immortal, nonce, tip: 0 };
+	let payload = signing_payload(&call, &extra, now).unwrap();
+	let signature = Signature::Sr25519(pair.sign(&payload));
+	BasicExtrinsic::new_signed(call, (MultiAddress::Id(H256(pair.public().0)), signature, extra))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		consensus::AuraAuthorities,
+		extensions::BlockHash,
+		offchain::{Heartbeat, HeartbeatBlock, LastHeartbeat},
+		storage::StorageMap,
+		upgrade::{AuthorizedUpgrade, PendingCode},
+		AsCompact,
+	};
+	use sp_core::Pair;
+
+	#[test]
+	fn encodes_like_unchecked_extrinsic() {
+		let unsigned = BasicExtrinsic::new_unsigned(Call::Flip);
+		assert_eq!(
+			unsigned.encode(),
+			vec![3 << 2, EXTRINSIC_FORMAT_VERSION, FLIPPER_PALLET_INDEX, 0]
+		);
+		assert_eq!(RuntimeCall::Flipper(Call::Flip).encode(), vec![FLIPPER_PALLET_INDEX, 0]);
+		assert_eq!(BasicExtrinsic::decode(&mut &unsigned.encode()[..]), Ok(unsigned));
+		assert!(BasicExtrinsic::decode(&mut &[2 << 2, 1, 0][..]).is_err());
+
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			BlockHash::insert(&0, &H256::repeat_byte(1));
+			let pair = sp_core::sr25519::Pair::from_string("//Alice", None).unwrap();
+			let signed = sign(&pair, Call::Add(AsCompact(5)), 0, 1);
+			let encoded = signed.encode();
+			assert_eq!(encoded[2], EXTRINSIC_FORMAT_VERSION | SIGNED_BIT);
+			assert_eq!(BasicExtrinsic::decode(&mut &encoded[..]), Ok(signed.clone()));
+
+			let checked = signed.clone().check(1).unwrap();
+			assert_eq!(checked.signed.map(|(who, _)| who), Some(H256(pair.public().0)));
+
+			// Signed for another chain.
+			BlockHash::insert(&0, &H256::repeat_byte(2));
+			assert_eq!(signed.clone().check(1).err(), Some(InvalidTransaction::BadProof.into()));
+			// Past blocks are re-executed without checking signatures.
+			let checked = signed.check_with(1, false).unwrap();
+			assert_eq!(checked.signed.map(|(who, _)| who), Some(H256(pair.public().0)));
+		});
+	}
+
+	#[test]
+	fn limits_call_depth() {
+		let nested = |depth| {
+			let call =
+				(0..depth).fold(Call::Flip, |call, _| Call::Propose { call: Box::new(call) });
+			BasicExtrinsic::new_unsigned(call).encode()
+		};
+		assert!(BasicExtrinsic::decode(&mut &nested(MAX_EXTRINSIC_DEPTH - 1)[..]).is_ok());
+		assert!(BasicExtrinsic::decode(&mut &nested(MAX_EXTRINSIC_DEPTH + 1)[..]).is_err());
+	}
+
+	#[test]
+	fn verifies_ed25519_and_ecdsa() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			BlockHash::insert(&0, &H256::repeat_byte(1));
+			let extra = SignedExtra { era: sp_runtime::generic::Era::Immortal, nonce: 0, tip: 0 };
+			let payload = signing_payload(&Call::Flip, &extra, 1).unwrap();
+			let signed = |who: H256, signature: Signature| {
+				BasicExtrinsic::new_signed(
+					Call::Flip,
+					(MultiAddress::Id(who), signature, extra.clone()),
+				)
+				.check(1)
+				.map(|checked| checked.signed.map(|(who, _)| who))
+			};
+
+			let ed25519 = sp_core::ed25519::Pair::from_string("//Alice", None).unwrap();
+			let who = H256(ed25519.public().0);
+			assert_eq!(signed(who, Signature::Ed25519(ed25519.sign(&payload))), Ok(Some(who)));
+
+			let ecdsa = sp_core::ecdsa::Pair::from_string("//Alice", None).unwrap();
+			let who = H256(sp_io::hashing::blake2_256(ecdsa.public().as_ref()));
+			assert_eq!(signed(who, Signature::Ecdsa(ecdsa.sign(&payload))), Ok(Some(who)));
+			// The address is the hash of the key, not the key.
+			let mut key = [0; 32];
+			key.copy_from_slice(&ecdsa.public().as_ref()[1..]);
+			assert_eq!(
+				signed(H256(key), Signature::Ecdsa(ecdsa.sign(&payload))),
+				Err(InvalidTransaction::BadProof.into())
+			);
+		});
+	}
+
+	#[test]
+	fn validates_unsigned_per_call() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let code = b"new runtime".to_vec();
+			let call = Call::ApplyAuthorizedUpgrade { code: code.clone() };
+			assert_eq!(validate_unsigned(&call, 1), Err(InvalidTransaction::Call.into()));
+			assert_eq!(
+				validate_unsigned(&Call::Flip, 1),
+				Err(UnknownTransaction::NoUnsignedValidator.into())
+			);
+			// Unsigned additions only come as authority heartbeats.
+			assert_eq!(
+				validate_unsigned(&Call::Add(AsCompact(1)), 1),
+				Err(UnknownTransaction::NoUnsignedValidator.into())
+			);
+
+			let code_hash = H256(sp_io::hashing::blake2_256(&code));
+			upgrade::Authorized::put(&AuthorizedUpgrade { code_hash, enact_at: 10 });
+			assert!(validate_unsigned(&call, 1).is_ok());
+
+			PendingCode::put(&code);
+			assert_eq!(validate_unsigned(&call, 1), Err(InvalidTransaction::Stale.into()));
+		});
+	}
+
+	#[test]
+	fn validates_heartbeats() {
+		let alice = sp_core::sr25519::Pair::from_string("//Alice", None).unwrap();
+		let bob = sp_core::sr25519::Pair::from_string("//Bob", None).unwrap();
+		let heartbeat = |pair: &sp_core::sr25519::Pair, block_number| {
+			let heartbeat = Heartbeat { block_number, authority_index: 0 };
+			let signature = pair.sign(&heartbeat.encode());
+			Call::Heartbeat { heartbeat, signature }
+		};
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			AuraAuthorities::put(&vec![alice.public().into()]);
+
+			assert!(validate_unsigned(&heartbeat(&alice, 4), 5).is_ok());
+			assert_eq!(
+				validate_unsigned(&heartbeat(&bob, 4), 5),
+				Err(InvalidTransaction::BadProof.into())
+			);
+			assert_eq!(
+				validate_unsigned(&heartbeat(&alice, 5), 5),
+				Err(InvalidTransaction::Future.into())
+			);
+
+			// Block 5 took a heartbeat from block 4: neither another one in block 5 nor a replay
+			// later is valid.
+			HeartbeatBlock::put(&5);
+			LastHeartbeat::put(&4);
+			assert_eq!(
+				validate_unsigned(&heartbeat(&alice, 3), 5),
+				Err(InvalidTransaction::Stale.into())
+			);
+			assert_eq!(
+				validate_unsigned(&heartbeat(&alice, 4), 6),
+				Err(InvalidTransaction::Stale.into())
+			);
+			assert!(validate_unsigned(&heartbeat(&alice, 5), 6).is_ok());
+		});
+	}
+}
@@ -0,0 +1,74 @@
+//! The `Add` and `Multiply` calls of every account, kept off chain.
+//!
+//! `apply_extrinsic` writes a [`HistoryRecord`] of each signed `Add` or `Multiply` to the offchain
+//! database with `sp_io::offchain_index`, under the [`history_key`] of its signer and nonce. The
+//! state only keeps the nonces, which bound the keys a reader has to look up, see the node's
+//! `flipper_history` RPC. Nothing is written unless the node runs with offchain indexing enabled.
+//!
+//! Blocks are indexed when imported, so an extrinsic of a block which gets retracted stays in the
+//! history until the signer uses its nonce again.
+
+use crate::{extensions::Nonces, storage::StorageMap, Address, BlockNumber, Call, Nonce};
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_std::prelude::*;
+
+/// The prefix of the offchain database keys of history records.
+pub const HISTORY_PREFIX: &[u8] = b"flipper::history";
+
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub struct HistoryRecord {
+	pub block: BlockNumber,
+	/// The index of the extrinsic in its block.
+	pub index: u32,
+	pub call: Call,
+	/// Whether the call was dispatched without error.
+	pub succeeded: bool,
+}
+
+/// Where the record of the extrinsic `who` signed with `nonce` is kept.
+pub fn history_key(who: &Address, nonce: Nonce) -> Vec<u8> {
+	(HISTORY_PREFIX, who, nonce).encode()
+}
+
+/// The state key of the nonce of `who`: it has signed no more extrinsics than that.
+pub fn nonce_key(who: &Address) -> Vec<u8> {
+	Nonces::hashed_key_for(who)
+}
+
+/// Whether `call` belongs in the history.
+pub fn is_recorded(call: &Call) -> bool {
+	matches!(call, Call::Add(_) | Call::Multiply(_))
+}
+
+pub fn note(who: &Address, nonce: Nonce, record: &HistoryRecord) {
+	sp_io::offchain_index::set(&history_key(who, nonce), &record.encode());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::AsCompact;
+	use sp_core::H256;
+
+	#[test]
+	fn indexes_records_by_signer_and_nonce() {
+		let alice = H256::repeat_byte(1);
+		let record =
+			HistoryRecord { block: 3, index: 1, call: Call::Add(AsCompact(2)), succeeded: true };
+
+		let mut ext = sp_io::TestExternalities::new_empty();
+		ext.execute_with(|| note(&alice, 4, &record));
+		ext.persist_offchain_overlay();
+
+		let db = ext.offchain_db();
+		let stored = db.get(&history_key(&alice, 4)).expect("record was indexed");
+		assert_eq!(HistoryRecord::decode(&mut &stored[..]), Ok(record));
+		assert_eq!(db.get(&history_key(&alice, 3)), None);
+		assert!(is_recorded(&Call::Multiply(AsCompact(2))));
+		assert!(!is_recorded(&Call::Flip));
+	}
+}
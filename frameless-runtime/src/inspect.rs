@@ -0,0 +1,93 @@
+//! Human readable storage, for the node's offline tools such as `replay`.
+
+use crate::{
+	balances::{Balance, Balances},
+	events::{EventRecord, Events},
+	extensions::Nonces,
+	storage::{StorageMap, StorageValue, HASH_LEN},
+	Address, Bit, Nonce, Value,
+};
+use parity_scale_codec::{Decode, DecodeAll};
+
+/// The storage items the tools know about.
+enum Item {
+	Bit,
+	Value,
+	Events,
+	Balance(Address),
+	Nonce(Address),
+}
+
+/// The key of the entry of `S` at `key`, if that is one.
+fn map_key<S: StorageMap>(key: &[u8]) -> Option<S::Key> {
+	let hashed = key.strip_prefix(&S::prefix()[..])?;
+	S::Key::decode_all(&mut hashed.get(HASH_LEN..)?).ok()
+}
+
+fn item(key: &[u8]) -> Option<Item> {
+	if key == Bit::hashed_key() {
+		Some(Item::Bit)
+	} else if key == Value::hashed_key() {
+		Some(Item::Value)
+	} else if key == Events::hashed_key() {
+		Some(Item::Events)
+	} else if let Some(who) = map_key::<Balances>(key) {
+		Some(Item::Balance(who))
+	} else {
+		map_key::<Nonces>(key).map(Item::Nonce)
+	}
+}
+
+/// The name of the storage item at `key`, e.g. `Balances::Account(0x..)`.
+pub fn name(key: &[u8]) -> Option<String> {
+	item(key).map(|item| match item {
+		Item::Bit => "Flipper::Bit".into(),
+		Item::Value => "Flipper::Value".into(),
+		Item::Events => "System::Events".into(),
+		Item::Balance(who) => format!("Balances::Account({:?})", who),
+		Item::Nonce(who) => format!("System::Nonces({:?})", who),
+	})
+}
+
+/// `value`, stored at `key`, decoded as what is stored there.
+pub fn decode(key: &[u8], value: &[u8]) -> Option<String> {
+	fn to_string<T: Decode + ToString>(value: &[u8]) -> Option<String> {
+		T::decode_all(&mut &value[..]).ok().map(|value| value.to_string())
+	}
+	match item(key)? {
+		Item::Bit => to_string::<bool>(value),
+		Item::Value => to_string::<u32>(value),
+		Item::Events => decode_events(value).map(|events| format!("{} events", events.len())),
+		Item::Balance(_) => to_string::<Balance>(value),
+		Item::Nonce(_) => to_string::<Nonce>(value),
+	}
+}
+
+/// Where the events of the current block are kept.
+pub fn events_key() -> Vec<u8> {
+	Events::hashed_key().to_vec()
+}
+
+/// The events stored under [`events_key`], one per line.
+pub fn decode_events(value: &[u8]) -> Option<Vec<String>> {
+	let records = Vec::<EventRecord>::decode(&mut &value[..]).ok()?;
+	Some(records.iter().map(|record| format!("{:?}", record)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parity_scale_codec::Encode;
+	use sp_core::H256;
+
+	#[test]
+	fn names_and_decodes_known_items() {
+		let alice = H256::repeat_byte(1);
+		let balance = Balances::hashed_key_for(&alice);
+		assert_eq!(name(&balance), Some(format!("Balances::Account({:?})", alice)));
+		assert_eq!(decode(&balance, &10u128.encode()), Some("10".into()));
+		assert_eq!(decode(&Bit::hashed_key(), &true.encode()), Some("true".into()));
+		assert_eq!(name(&Nonces::prefix()), None);
+		assert_eq!(name(b"value"), None);
+	}
+}
@@ -4,6 +4,7 @@
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
 use parity_scale_codec::{Decode, Encode, HasCompact};
+use scale_info::TypeInfo;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 
 use log::info;
@@ -12,10 +13,8 @@ use sp_api::impl_runtime_apis;
 use sp_block_builder::runtime_decl_for_BlockBuilder::BlockBuilder;
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys,
-	traits::{BlakeTwo256, Block as BlockT, Extrinsic},
-	transaction_validity::{
-		InvalidTransaction, TransactionSource, TransactionValidity, ValidTransaction,
-	},
+	traits::{BlakeTwo256, Block as BlockT, Hash as HashT},
+	transaction_validity::{InvalidTransaction, TransactionSource, TransactionValidity},
 	ApplyExtrinsicResult, BoundToRuntimeAppPublic,
 };
 use sp_std::prelude::*;
@@ -35,8 +34,60 @@ use sp_version::RuntimeVersion;
 use serde::{Deserialize, Serialize};
 use sp_runtime::transaction_validity::TransactionValidityError;
 
-type Address = sp_core::H256;
-type Signature = sp_core::H512;
+#[macro_use]
+mod storage;
+mod admin;
+mod balances;
+#[cfg(all(feature = "std", feature = "runtime-benchmarks"))]
+pub mod benchmarking;
+mod consensus;
+mod dispatch;
+mod events;
+mod extensions;
+mod extrinsic;
+pub mod history;
+#[cfg(feature = "std")]
+pub mod inspect;
+mod metadata;
+mod migrations;
+mod multisig;
+mod offchain;
+mod rewards;
+mod scheduler;
+mod upgrade;
+
+pub mod apis;
+pub mod weights;
+pub use dispatch::DispatchError;
+pub use extrinsic::BasicExtrinsic;
+pub use scheduler::{Scheduled, ScheduledId};
+
+use storage::{StorageMap, StorageValue};
+
+/// An account: the public key of sr25519 and ed25519 signers, the `blake2_256` hash of the
+/// compressed public key of ecdsa signers.
+pub type Address = sp_core::H256;
+pub type Signature = sp_runtime::MultiSignature;
+pub use balances::{Balance, TOKEN_DECIMALS, TOKEN_SYMBOL};
+
+/// The prefix of the SS58 encoding of addresses on this chain.
+pub const SS58_PREFIX: u16 = 4242;
+
+/// The Aura slot, so the time between blocks.
+pub const SLOT_DURATION_MS: u64 = 3000;
+
+/// Everything wallets show of an account, see [`apis::AccountApi`].
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone, Default)]
+pub struct AccountInfo {
+	/// The nonce the next extrinsic of the account has to use.
+	pub nonce: Nonce,
+	pub free: Balance,
+	/// Nothing reserves balance yet, so this is always zero.
+	pub reserved: Balance,
+	pub is_admin: bool,
+}
+pub use extensions::Nonce;
 
 /*
 curl http://localhost:9933 -H "Content-Type:application/json;charset=utf-8" -d   '{
@@ -50,7 +101,7 @@ curl http://localhost:9933 -H "Content-Type:application/json;charset=utf-8" -d
 	"jsonrpc":"2.0",
 	"id":1,
 	"method":"state_getStorage",
-	"params": ["0x626F6F6C65616E"]
+	"params": ["0x49a00072897c66ebf5008759933aca836e10e8e8b1490658f929880b389614e9"]
 }'
 */
 
@@ -98,10 +149,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("frameless-runtime"),
 	impl_name: create_runtime_str!("frameless-runtime"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 5,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 2,
 	state_version: 1,
 };
 
@@ -112,68 +163,267 @@ pub fn native_version() -> NativeVersion {
 }
 
 /// The type that provides the genesis storage values for a new chain
-#[cfg_attr(feature = "std", derive(Serialize, Deserialize, Default))]
-pub struct GenesisConfig;
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize, Default, Clone))]
+#[cfg_attr(feature = "std", serde(default))]
+pub struct GenesisConfig {
+	/// The accounts allowed to propose and approve admin calls.
+	pub admins: Vec<Address>,
+	/// The number of admin approvals needed to dispatch an admin call.
+	pub admin_threshold: u32,
+	/// The initial block authors and finality voters.
+	pub authorities: Vec<(AuraId, sp_finality_grandpa::AuthorityId)>,
+	/// The initial balances.
+	pub balances: Vec<(Address, Balance)>,
+	/// The initial flipper bit, if any.
+	pub bit: Option<bool>,
+	/// The initial adder/multiplier value, if any.
+	pub value: Option<u32>,
+	/// Minted for the author of every block.
+	pub block_reward: Balance,
+}
 
 #[cfg(feature = "std")]
 impl BuildStorage for GenesisConfig {
 	fn assimilate_storage(&self, storage: &mut Storage) -> Result<(), String> {
-		// we have nothing to put into storage in genesis, except this:
 		storage.top.insert(well_known_keys::CODE.into(), WASM_BINARY.unwrap().to_vec());
+		insert_admins(storage, &self.admins, self.admin_threshold)?;
+		insert_authorities(storage, &self.authorities)?;
+
+		for (who, balance) in &self.balances {
+			storage.top.insert(balances::Balances::hashed_key_for(who), balance.encode());
+		}
+
+		if let Some(bit) = self.bit {
+			storage.top.insert(Bit::hashed_key().to_vec(), bit.encode());
+		}
+		if let Some(value) = self.value {
+			storage.top.insert(Value::hashed_key().to_vec(), value.encode());
+		}
+		if self.block_reward > 0 {
+			storage
+				.top
+				.insert(rewards::BlockReward::hashed_key().to_vec(), self.block_reward.encode());
+		}
+
+		// A fresh chain has nothing to migrate.
+		storage.top.insert(
+			migrations::LastRuntimeUpgrade::hashed_key().to_vec(),
+			VERSION.spec_version.encode(),
+		);
+		Ok(())
+	}
+}
+
+/// Changes to the state of an existing chain, e.g. a raw chain spec written by `export-state`, to
+/// start a new network from it. The new network starts over from block zero, so whatever refers to
+/// the block numbers of the old one is dropped along the way.
+#[cfg(feature = "std")]
+#[derive(Default, Clone, Debug)]
+pub struct GenesisOverrides {
+	/// New admins and the number of approvals they need.
+	pub admins: Option<(Vec<Address>, u32)>,
+	/// New block authors and finality voters.
+	pub authorities: Option<Vec<(AuraId, sp_finality_grandpa::AuthorityId)>>,
+}
+
+#[cfg(feature = "std")]
+impl BuildStorage for GenesisOverrides {
+	fn assimilate_storage(&self, storage: &mut Storage) -> Result<(), String> {
+		if let Some((admins, threshold)) = &self.admins {
+			insert_admins(storage, admins, *threshold)?;
+			// Approvals of the previous admins must not count.
+			storage.top.retain(|key, _| !key.starts_with(&multisig::Proposals::prefix()));
+		}
+		if let Some(authorities) = &self.authorities {
+			insert_authorities(storage, authorities)?;
+			// The new network starts with the first GRANDPA set.
+			storage.top.remove(&consensus::GrandpaSetId::hashed_key()[..]);
+		}
+
+		storage.top.insert(Number::hashed_key().to_vec(), (0 as BlockNumber).encode());
+		for prefix in [
+			extensions::BlockHash::prefix(),
+			scheduler::Agenda::prefix(),
+			scheduler::Lookup::prefix(),
+		] {
+			storage.top.retain(|key, _| !key.starts_with(&prefix));
+		}
+		for key in [
+			upgrade::Authorized::hashed_key(),
+			upgrade::PendingCode::hashed_key(),
+			offchain::LastHeartbeat::hashed_key(),
+			offchain::HeartbeatBlock::hashed_key(),
+		] {
+			storage.top.remove(&key[..]);
+		}
 		Ok(())
 	}
 }
 
+#[cfg(feature = "std")]
+fn insert_admins(storage: &mut Storage, admins: &[Address], threshold: u32) -> Result<(), String> {
+	admin::check_admins(admins, threshold).map_err(|e| format!("Invalid admins: {:?}", e))?;
+	storage.top.insert(admin::Admins::hashed_key().to_vec(), admins.encode());
+	storage.top.insert(admin::Threshold::hashed_key().to_vec(), threshold.encode());
+	Ok(())
+}
+
+#[cfg(feature = "std")]
+fn insert_authorities(
+	storage: &mut Storage,
+	authorities: &[(AuraId, sp_finality_grandpa::AuthorityId)],
+) -> Result<(), String> {
+	let (aura, grandpa): (Vec<_>, Vec<_>) = authorities.iter().cloned().unzip();
+	consensus::check_authorities(&aura, &grandpa)
+		.map_err(|e| format!("Invalid authorities: {:?}", e))?;
+	storage
+		.top
+		.insert(consensus::AuraAuthorities::hashed_key().to_vec(), aura.encode());
+	storage.top.insert(
+		consensus::GrandpaAuthorities::hashed_key().to_vec(),
+		consensus::grandpa_weighted(grandpa).encode(),
+	);
+	Ok(())
+}
+
 /// Block header type as expected by this runtime.
 pub type Header = generic::Header<BlockNumber, BlakeTwo256>;
 /// Block type as expected by this runtime.
 pub type Block = generic::Block<Header, BasicExtrinsic>;
 
-// this extrinsic type does nothing other than fulfill the compiler.
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize, parity_util_mem::MallocSizeOf))]
-#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
-pub struct BasicExtrinsic {
-	call: crate::Call,
-	signature: (Address, Signature, AsCompact<u32>),
-}
-
-#[cfg_attr(feature = "std", derive(Serialize, Deserialize, parity_util_mem::MallocSizeOf))]
-#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
 pub struct AsCompact<T: HasCompact>(#[codec(compact)] T);
 
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize, parity_util_mem::MallocSizeOf))]
-#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
 pub enum Call {
 	Flip,
 	Add(AsCompact<u32>),
 	Multiply(AsCompact<u32>),
-	Upgrade { password: Vec<u8>, payload: Vec<u8> },
-	Kill { password: Vec<u8> },
+	Upgrade {
+		password: Vec<u8>,
+		payload: Vec<u8>,
+	},
+	Kill {
+		password: Vec<u8>,
+	},
+	/// Admin only: allow code with `blake2_256` hash `code_hash` to be enacted at `enact_at`.
+	AuthorizeUpgrade {
+		code_hash: sp_core::H256,
+		enact_at: BlockNumber,
+	},
+	/// Upload the code for an authorized upgrade. Anyone may submit this.
+	ApplyAuthorizedUpgrade {
+		code: Vec<u8>,
+	},
+	/// Admin only: refuse all but admin calls until resumed.
+	Pause,
+	/// Admin only: lift a pause.
+	Resume,
+	/// Admin only: replace the admins and the number of approvals they need.
+	SetAdmins {
+		admins: Vec<Address>,
+		threshold: u32,
+	},
+	/// Admin only: replace the sr25519 Aura and ed25519 Grandpa authority keys.
+	SetAuthorities {
+		aura: Vec<sp_core::H256>,
+		grandpa: Vec<sp_core::H256>,
+	},
+	/// Admins only: propose an admin call, see `multisig`.
+	Propose {
+		call: Box<Call>,
+	},
+	/// Admins only: approve a proposed admin call.
+	Approve {
+		id: u32,
+	},
+	/// Dispatch each call, reporting each outcome as an event.
+	Batch(Vec<Call>),
+	/// Dispatch all calls, or none of them if one fails.
+	BatchAll(Vec<Call>),
+	/// Dispatch `call` on behalf of the sender at the start of block `when`.
+	Schedule {
+		when: BlockNumber,
+		call: Box<Call>,
+	},
+	/// Cancel a call scheduled by the sender.
+	CancelScheduled {
+		id: u32,
+	},
+	/// Admin only: set the reward minted for the author of every block.
+	SetBlockReward {
+		reward: Balance,
+	},
+	/// Unsigned: add 1 to the value on behalf of an authority, see [`offchain`].
+	Heartbeat {
+		heartbeat: offchain::Heartbeat,
+		#[cfg_attr(feature = "std", ignore_malloc_size_of = "fixed size")]
+		signature: sp_core::sr25519::Signature,
+	},
 }
 
-impl Extrinsic for BasicExtrinsic {
-	type Call = crate::Call;
-	type SignaturePayload = Signature;
+storage_value! {
+	/// The header of the block being built, between `initialize_block` and `finalize_block`.
+	pub struct CurrentHeader(b"System", b"Header"): Header;
+}
 
-	fn new(data: Self::Call, _signature: Option<Self::SignaturePayload>) -> Option<Self> {
-		todo!()
-		//Some(Self { action: data, salt: 0 })
-	}
+storage_value! {
+	/// The number of the latest block, kept after `finalize_block` for validating transactions.
+	pub struct Number(b"System", b"Number"): BlockNumber;
+}
+
+storage_value! {
+	/// The index of the extrinsic being applied, if any.
+	pub struct ExtrinsicIndex(b"System", b"ExtrinsicIndex"): u32;
+}
+
+storage_value! {
+	/// The [`weights::Weight::total_nanos`] of the extrinsics applied so far in the current block.
+	pub struct BlockWeight(b"System", b"BlockWeight"): u64;
+}
+
+storage_map! {
+	/// The encoded extrinsics applied so far in the block being built, by index.
+	pub struct ExtrinsicData(b"System", b"ExtrinsicData"): map u32 => Vec<u8>;
+}
+
+storage_value! {
+	/// The flipper bit: 0x49a00072897c66ebf5008759933aca836e10e8e8b1490658f929880b389614e9
+	pub struct Bit(b"Flipper", b"Bit"): bool;
+}
+
+storage_value! {
+	/// The adder/multiplier value: 0x49a00072897c66ebf5008759933aca836b2f21989c43cc4e06ac1ad3e2027000
+	pub struct Value(b"Flipper", b"Value"): u32;
 }
 
-// 686561646572 raw storage key
-pub const HEADER_KEY: [u8; 6] = *b"header";
-const BIT_KEY: [u8; 3] = *b"bit";
-const VALUE_KEY: [u8; 5] = *b"value";
 const KILL_PASSWORD: [u8; 3] = *b"bye";
 const UPGRADE_PASSWORD: [u8; 12] = *b"obsolescence";
 const EMOJI: &str = "🤖";
-const ADMIN_KEY: [u8; 32] = [
+/// The admin before admins were stored on chain, see `migrations::v2`.
+pub const ADMIN_KEY: [u8; 32] = [
 	218, 135, 45, 83, 176, 223, 163, 56, 226, 163, 235, 10, 53, 205, 14, 196, 91, 7, 146, 153, 110,
 	122, 20, 219, 84, 110, 62, 121, 221, 28, 157, 72,
 ];
 
+/// The number of the block being built.
+fn block_number() -> BlockNumber {
+	CurrentHeader::get().map_or(0, |header| header.number)
+}
+
+/// Adds `item` to the digest of the block being built.
+fn deposit_log(item: generic::DigestItem) {
+	CurrentHeader::mutate(|header| {
+		if let Some(header) = header {
+			header.digest.push(item)
+		}
+	});
+}
+
 /// The main struct in this module. In frame this comes from `construct_runtime!`
+#[derive(TypeInfo)]
 pub struct Runtime;
 
 impl_runtime_apis! {
@@ -185,94 +435,130 @@ impl sp_api::Core<Block> for Runtime {
 
 	fn execute_block(block: Block) {
 		info!(target: "frameless", "🖼{EMOJI}️ Entering execute_block. block: {:?}", block);
-		Self::initialize_block(&block.header);
+		execute(block, true)
+	}
+
+	fn initialize_block(header: &<Block as BlockT>::Header) {
+		info!(target: "frameless", "🖼{EMOJI}️ Entering initialize_block. header: {:?}", header);
+		CurrentHeader::put(header);
+		Number::put(&header.number);
+		extensions::note_parent_hash(header.number, header.parent_hash);
+		events::reset_events();
+		rewards::note_author(&header.digest);
+		migrations::on_runtime_upgrade();
+		upgrade::enact_authorized_upgrade(header.number);
+		scheduler::on_initialize(header.number);
+	}
+}
 
-		for _transaction in block.extrinsics {
-			// we have no notion of transaction, so nothing to execute yet.
-			todo!();
+/// Executes `block`, checking the signatures of its extrinsics only with `signature_check`, see
+/// [`extrinsic::BasicExtrinsic::check_with`].
+fn execute(block: Block, signature_check: bool) {
+	<Runtime as sp_api::runtime_decl_for_Core::Core<Block>>::initialize_block(&block.header);
+
+	let extrinsics = block.extrinsics.iter().map(Encode::encode).collect();
+	assert_eq!(
+		extrinsics_root(extrinsics),
+		block.header.extrinsics_root,
+		"extrinsics root does not match the header"
+	);
+
+	for extrinsic in block.extrinsics {
+		if let Err(e) = apply(extrinsic, signature_check) {
+			panic!("block contains an invalid extrinsic: {:?}", e);
 		}
+	}
 
+	// The client checks the state root against the header.
+	Runtime::finalize_block();
+}
 
-		Self::finalize_block();
+/// Applies `extrinsic`, checking its signature only with `signature_check`.
+fn apply(extrinsic: BasicExtrinsic, signature_check: bool) -> ApplyExtrinsicResult {
+	let encoded = extrinsic.encode();
+	let extrinsic = extrinsic.check_with(block_number(), signature_check)?;
+	if admin::is_paused() && !extrinsic.call.is_allowed_while_paused(&extrinsic.origin()) {
+		return Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(admin::PAUSED)))
 	}
 
-	fn initialize_block(header: &<Block as BlockT>::Header) {
-		info!(target: "frameless", "🖼{EMOJI}️ Entering initialize_block. header: {:?}", header);
-		sp_io::storage::set(&HEADER_KEY, &header.encode());
+	// Leaves the extrinsic to a later block, which the block builder understands.
+	let weight = extrinsic_weight(&extrinsic);
+	let block_weight = BlockWeight::get().unwrap_or(0).saturating_add(weight);
+	if block_weight > weights::MAXIMUM_BLOCK_NANOS {
+		return Err(InvalidTransaction::ExhaustsResources.into())
+	}
+
+	let signer = extrinsic.signed.as_ref().map(|(who, extra)| (*who, extra.nonce));
+	let origin = match extrinsic.signed {
+		Some((who, extra)) => {
+			extensions::pre_dispatch(&who, &extra)?;
+			dispatch::Origin::Signed(who)
+		},
+		None => {
+			// Block authors could put in anything, so unsigned calls are checked here as well.
+			crate::extrinsic::validate_unsigned(&extrinsic.call, block_number())?;
+			dispatch::Origin::None
+		},
+	};
+
+	let index = ExtrinsicIndex::get().unwrap_or(0);
+	ExtrinsicIndex::put(&index);
+	// From here on the extrinsic is part of the block, whatever its call does.
+	ExtrinsicData::insert(&index, &encoded);
+	BlockWeight::put(&block_weight);
+	// Only clone the calls the history keeps, rather than e.g. runtime code.
+	let recorded = history::is_recorded(&extrinsic.call).then(|| extrinsic.call.clone());
+	// A failing call leaves no writes behind, but the nonce and tip taken above stay taken.
+	let result = storage::with_transaction(|| dispatch::dispatch(extrinsic.call, origin));
+	if let Err(error) = &result {
+		info!(target: "frameless", "🖼{EMOJI}️ extrinsic {index} failed: {:?}", error);
+		events::deposit_event(events::Event::ExtrinsicFailed { error: error.clone() });
+	}
+	if let (Some((who, nonce)), Some(call)) = (signer, recorded) {
+		let record = history::HistoryRecord {
+			block: block_number(),
+			index,
+			call,
+			succeeded: result.is_ok(),
+		};
+		history::note(&who, nonce, &record);
+	}
+	ExtrinsicIndex::put(&(index + 1));
+
+	Ok(result.map_err(Into::into))
+}
+
+/// The time applying `extrinsic` takes, see [`weights`].
+fn extrinsic_weight(extrinsic: &extrinsic::CheckedExtrinsic) -> u64 {
+	let weight = extrinsic.call.weight();
+	match extrinsic.signed {
+		Some(_) => weights::EXTRINSIC_BASE.saturating_add(weight).total_nanos(),
+		None => weight.total_nanos(),
 	}
 }
 
+/// The root the client expects in `header.extrinsics_root`, over the encoded extrinsics in order.
+fn extrinsics_root(extrinsics: Vec<Vec<u8>>) -> sp_core::H256 {
+	BlakeTwo256::ordered_trie_root(extrinsics, VERSION.state_version())
+}
+
 // https://substrate.dev/rustdocs/master/sc_block_builder/trait.BlockBuilderApi.html
 impl sp_block_builder::BlockBuilder<Block> for Runtime {
 	fn apply_extrinsic(extrinsic: <Block as BlockT>::Extrinsic) -> ApplyExtrinsicResult {
 		info!(target: "frameless", "🖼{EMOJI}️ Entering apply_extrinsic: {:?}", extrinsic);
-
-			let (address,signature, _) = extrinsic.signature;
-			let signature = sp_core::sr25519::Signature::from_raw(signature.0);
-			let address = sp_core::sr25519::Public::from_raw(address.0);
-			if !sp_io::crypto::sr25519_verify(&signature, &extrinsic.call.encode(), &address){
-				return Err(
-					TransactionValidityError::Invalid(InvalidTransaction::BadSigner));
-			}
-
-		match extrinsic.call {
-			Call::Flip => {
-				let mut bit = sp_io::storage::get(&BIT_KEY)
-					.map_or(false, |v| bool::decode(&mut &*v).unwrap_or(false));
-					info!(target: "flipper", "{EMOJI} current bit: {bit}");
-					bit = !bit;
-					sp_io::storage::set(&BIT_KEY, &bit.encode());
-					info!(target: "flipper", "{EMOJI} stored flipped bit: {bit}");
-			},
-			Call::Add(value) => {
-					let existing = sp_io::storage::get(&VALUE_KEY)
-					.map_or(0, |v| u32::decode(&mut &*v).unwrap_or(0));
-					info!(target: "adder", "{EMOJI} existing value: {existing} supplied value: {}", value.0);
-					let result = existing + value.0;
-					sp_io::storage::set(&VALUE_KEY, &result.encode());
-					info!(target: "adder", "{EMOJI} stored result: {result}");
-			},
-			Call::Multiply(value) => {
-				let existing = sp_io::storage::get(&VALUE_KEY)
-					.map_or(1, |v| u32::decode(&mut &*v).unwrap_or(1));
-					info!(target: "multiplier", "{EMOJI} existing value: {existing} supplied value: {}", value.0);
-					let result = existing * value.0;
-				sp_io::storage::set(&VALUE_KEY, &result.encode());
-					info!(target: "multiplier", "{EMOJI} stored result: {result}");
-			},
-			Call::Upgrade{password, payload, ..} => {
-				if password == UPGRADE_PASSWORD && address.0 == ADMIN_KEY {
-						info!(target: "upgrader", "{EMOJI} upgrade initiated");
-						sp_io::storage::set(sp_storage::well_known_keys::CODE.into(), &payload);
-						}
-					else {
-						info!(target: "upgrader", "{EMOJI} upgrade rejected");
-					}
-			},
-			Call::Kill{password, ..} => {
-				if password == KILL_PASSWORD && address.0 == ADMIN_KEY {
-						info!(target: "killer", "{EMOJI} kill switch engaged");
-						sp_io::storage::set(sp_storage::well_known_keys::CODE.into(), &vec![]);
-						}
-					else {
-						info!(target: "killer", "{EMOJI} kill switch denied");
-					}
-
-			},
-		}
-
-		Ok(Ok(()))
+		apply(extrinsic, true)
 	}
 
 	fn finalize_block() -> <Block as BlockT>::Header {
 		info!(target: "frameless", "🖼{EMOJI}️ Entering finalize block.");
 
-		let raw_header = sp_io::storage::get(&HEADER_KEY)
+		let mut header = CurrentHeader::take()
 			.expect("We initialized with header, it never got mutated, qed");
-		sp_io::storage::clear(&HEADER_KEY);
-
-		let mut header = <Block as BlockT>::Header::decode(&mut &*raw_header)
-			.expect("we put a valid header in in the first place, qed");
+		let count = ExtrinsicIndex::take().unwrap_or(0);
+		BlockWeight::kill();
+		let extrinsics = (0..count).filter_map(|index| ExtrinsicData::take(&index)).collect();
+		header.extrinsics_root = extrinsics_root(extrinsics);
+		rewards::reward_author();
 		let raw_state_root = &sp_io::storage::root(sp_storage::StateVersion::default())[..];
 
 		header.state_root = sp_core::H256::decode(&mut &raw_state_root[..]).unwrap();
@@ -303,9 +589,23 @@ impl sp_transaction_pool::runtime_api::TaggedTransactionQueue<Block> for Runtime
 	) -> TransactionValidity {
 		info!(target: "frameless", "🖼{EMOJI}️ Entering validate_transaction. source: {:?}, tx: {:?}, block hash: {:?}", source, tx, block_hash);
 
-		// we don't know how to validate this -- It should be fine??
-		let data = tx.call;
-		Ok(ValidTransaction { provides: vec![data.encode()], ..Default::default() })
+		// Validate as part of the block built on top of `block_hash`.
+		let now = Number::get().unwrap_or(0) + 1;
+		extensions::note_parent_hash(now, block_hash);
+
+		let tx = tx.check(now)?;
+		if admin::is_paused() && !tx.call.is_allowed_while_paused(&tx.origin()) {
+			return Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(admin::PAUSED)))
+		}
+		// It would not fit in any block.
+		if extrinsic_weight(&tx) > weights::MAXIMUM_BLOCK_NANOS {
+			return Err(InvalidTransaction::ExhaustsResources.into())
+		}
+
+		match tx.signed {
+			Some((who, extra)) => extensions::validate(&who, &extra, now),
+			None => crate::extrinsic::validate_unsigned(&tx.call, now),
+		}
 	}
 }
 
@@ -313,13 +613,14 @@ impl sp_transaction_pool::runtime_api::TaggedTransactionQueue<Block> for Runtime
 
 impl sp_api::Metadata<Block> for Runtime {
 	fn metadata() -> OpaqueMetadata {
-		OpaqueMetadata::new(vec![0])
+		OpaqueMetadata::new(metadata::runtime_metadata().encode())
 	}
 }
 
 impl sp_offchain::OffchainWorkerApi<Block> for Runtime {
-	fn offchain_worker(_header: &<Block as BlockT>::Header) {
-		// we do not do anything.
+	fn offchain_worker(header: &<Block as BlockT>::Header) {
+		info!(target: "frameless", "🖼{EMOJI}️ Entering offchain_worker. block: {}", header.number);
+		offchain::offchain_worker(header.number);
 	}
 }
 
@@ -337,39 +638,70 @@ impl sp_session::SessionKeys<Block> for Runtime {
 // Here is the Aura API for the sake of making this runtime work with the node template node
 impl sp_consensus_aura::AuraApi<Block, AuraId> for Runtime {
 	fn slot_duration() -> sp_consensus_aura::SlotDuration {
-		// Three-second blocks
-		sp_consensus_aura::SlotDuration::from_millis(3000)
+		sp_consensus_aura::SlotDuration::from_millis(SLOT_DURATION_MS)
 	}
 
 	fn authorities() -> Vec<AuraId> {
-		// The only authority is Alice. This makes things work nicely in `--dev` mode
-		use sp_application_crypto::ByteArray;
+		consensus::AuraAuthorities::get().unwrap_or_default()
+	}
+}
+
+impl apis::SchedulerApi<Block> for Runtime {
+	fn pending() -> Vec<(BlockNumber, Scheduled)> {
+		scheduler::pending()
+	}
+}
+
+impl apis::AccountApi<Block> for Runtime {
+	fn account_info(who: Address) -> AccountInfo {
+		AccountInfo {
+			nonce: extensions::Nonces::get(&who).unwrap_or(0),
+			free: balances::free_balance(&who),
+			reserved: 0,
+			is_admin: admin::is_admin(&who),
+		}
+	}
+}
+
+impl apis::ErrorApi<Block> for Runtime {
+	fn decode_error(error: sp_runtime::DispatchError) -> Option<DispatchError> {
+		dispatch::decode_error(error)
+	}
+}
+
+impl apis::PropertiesApi<Block> for Runtime {
+	fn token_symbol() -> sp_runtime::RuntimeString {
+		TOKEN_SYMBOL.into()
+	}
+
+	fn token_decimals() -> u8 {
+		TOKEN_DECIMALS
+	}
+
+	fn ss58_prefix() -> u16 {
+		SS58_PREFIX
+	}
+}
+
+#[cfg(feature = "try-runtime")]
+impl apis::TryRuntime<Block> for Runtime {
+	fn on_runtime_upgrade() -> Vec<sp_runtime::RuntimeString> {
+		migrations::on_runtime_upgrade().into_iter().map(Into::into).collect()
+	}
 
-		vec![AuraId::from_slice(
-			&hex_literal::hex!("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d")
-				.to_vec(),
-		)
-		.unwrap()]
+	fn execute_block(block: Block, signature_check: bool) {
+		info!(target: "frameless", "🖼{EMOJI}️ Entering try execute_block. block: {:?}", block);
+		execute(block, signature_check)
 	}
 }
 
 impl sp_finality_grandpa::GrandpaApi<Block> for Runtime {
 	fn grandpa_authorities() -> sp_finality_grandpa::AuthorityList {
-		use sp_application_crypto::ByteArray;
-		vec![(
-			sp_finality_grandpa::AuthorityId::from_slice(
-				&hex_literal::hex!(
-					"88dc3417d5058ec4b4503e0c12ea1a0a89be200fe98922423d4334014fa6b0ee"
-				)
-				.to_vec(),
-			)
-			.unwrap(),
-			1,
-		)]
+		consensus::GrandpaAuthorities::get().unwrap_or_default()
 	}
 
 	fn current_set_id() -> sp_finality_grandpa::SetId {
-		0u64
+		consensus::GrandpaSetId::get().unwrap_or(0)
 	}
 
 	fn submit_report_equivocation_unsigned_extrinsic(
@@ -393,40 +725,284 @@ impl sp_finality_grandpa::GrandpaApi<Block> for Runtime {
 
 #[cfg(test)]
 mod tests {
-	use crate::{BasicExtrinsic, Call, Runtime, BIT_KEY};
-	use parity_scale_codec::{Decode, Encode};
-	use sp_application_crypto::Pair;
+	use crate::{
+		admin,
+		apis::{runtime_decl_for_AccountApi::AccountApi, runtime_decl_for_ErrorApi::ErrorApi},
+		balances, consensus,
+		events::{Event, EventRecord, Events, Phase},
+		extensions::{BlockHash, Nonces, SignedExtra},
+		extrinsic::{sign, signing_payload},
+		multisig, offchain, scheduler,
+		storage::{StorageMap, StorageValue},
+		upgrade, weights, AccountInfo, AsCompact, BasicExtrinsic, Bit, Block, BlockWeight, Call,
+		DispatchError, ExtrinsicData, GenesisOverrides, Header, Number, Runtime, Value,
+	};
+	use parity_scale_codec::Encode;
+	use sp_api::runtime_decl_for_Core::Core;
 	use sp_block_builder::runtime_decl_for_BlockBuilder::BlockBuilder;
+	use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+	use sp_core::Pair;
+	use sp_runtime::{
+		generic::Era,
+		traits::Header as _,
+		transaction_validity::{InvalidTransaction, TransactionValidityError},
+		BuildStorage, Storage,
+	};
 
 	#[test]
 	fn flips() {
 		const TEST_KEY: &str = "test key";
 		let pair: sp_core::sr25519::Pair = sp_core::Pair::generate_with_phrase(Some(TEST_KEY)).0;
 		let mut e = sp_io::TestExternalities::new_empty();
-		let call = Call::Flip;
-		let signature = (
-			sp_core::H256(pair.public().0),
-			sp_core::H512(pair.sign(&call.encode()).0),
-			AsCompat(0),
-		);
 		e.execute_with(|| {
-			let extrinsic = BasicExtrinsic { call, signature };
+			BlockHash::insert(&0, &sp_core::H256::repeat_byte(1));
 
 			// Check no existing value, apply extrinsic and expect resulting value as true
-			assert!(sp_io::storage::get(&BIT_KEY).is_none());
-			let _ = Runtime::apply_extrinsic(extrinsic).unwrap();
+			assert!(Bit::get().is_none());
+			let _ = Runtime::apply_extrinsic(sign(&pair, Call::Flip, 0, 0)).unwrap();
+			assert_eq!(Some(true), Bit::get());
+
+			// Replaying it is refused
 			assert_eq!(
-				true,
-				sp_io::storage::get(&BIT_KEY).map(|v| bool::decode(&mut &*v)).unwrap().unwrap()
+				Runtime::apply_extrinsic(sign(&pair, Call::Flip, 0, 0)),
+				Err(TransactionValidityError::Invalid(InvalidTransaction::Stale))
 			);
 
 			// Flip again and expect false
-			let extrinsic = BasicExtrinsic { call: Call::Flip, signature };
-			let _ = Runtime::apply_extrinsic(extrinsic).unwrap();
+			let _ = Runtime::apply_extrinsic(sign(&pair, Call::Flip, 1, 0)).unwrap();
+			assert_eq!(Some(false), Bit::get());
+		});
+	}
+	#[test]
+	fn reports_failed_extrinsics() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::from_string("//Alice", None).unwrap();
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			BlockHash::insert(&0, &sp_core::H256::repeat_byte(1));
+			Value::put(&u32::MAX);
+
+			let error = Runtime::apply_extrinsic(sign(&pair, Call::Add(AsCompact(1)), 0, 0))
+				.unwrap()
+				.unwrap_err();
+			assert_eq!(Runtime::decode_error(error), Some(DispatchError::Overflow));
+			assert_eq!(
+				Events::get().unwrap().pop().map(EventRecord::into_event),
+				Some(Event::ExtrinsicFailed { error: DispatchError::Overflow })
+			);
+			assert_eq!(Events::get().unwrap()[0].phase, Phase::ApplyExtrinsic(0));
+			// The nonce was used all the same.
+			assert_eq!(Nonces::get(&sp_core::Pair::public(&pair).0.into()), Some(1));
+		});
+	}
+
+	#[test]
+	fn rolls_back_failed_calls_but_keeps_nonce_and_fee() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::from_string("//Alice", None).unwrap();
+		let alice = sp_core::H256(pair.public().0);
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			BlockHash::insert(&0, &sp_core::H256::repeat_byte(1));
+			balances::Balances::insert(&alice, &10);
+			Value::put(&u32::MAX);
+			// `Flip` writes the bit, then the overflowing `Add` fails the call.
+			let call = Call::BatchAll(vec![Call::Flip, Call::Add(AsCompact(1))]);
+			let extra = SignedExtra { era: Era::Immortal, nonce: 0, tip: 3 };
+			let payload = signing_payload(&call, &extra, 0).unwrap();
+			let signature = sp_runtime::MultiSignature::Sr25519(pair.sign(&payload));
+			let extrinsic = BasicExtrinsic::new_signed(call, (alice.into(), signature, extra));
+
+			let result = Runtime::apply_extrinsic(extrinsic).unwrap();
+
+			assert_eq!(result.map_err(Runtime::decode_error), Err(Some(DispatchError::Overflow)));
+			assert_eq!(Bit::get(), None);
+			assert_eq!(Value::get(), Some(u32::MAX));
+			assert_eq!(Nonces::get(&alice), Some(1));
+			assert_eq!(balances::free_balance(&alice), 7);
+		});
+	}
+
+	#[test]
+	fn stops_filling_full_blocks() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::from_string("//Alice", None).unwrap();
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			BlockHash::insert(&0, &sp_core::H256::repeat_byte(1));
+			let flip = weights::EXTRINSIC_BASE.saturating_add(Call::Flip.weight()).total_nanos();
+			BlockWeight::put(&(weights::MAXIMUM_BLOCK_NANOS - flip));
+
+			assert_eq!(Runtime::apply_extrinsic(sign(&pair, Call::Flip, 0, 0)), Ok(Ok(())));
+			assert_eq!(BlockWeight::get(), Some(weights::MAXIMUM_BLOCK_NANOS));
+			assert_eq!(
+				Runtime::apply_extrinsic(sign(&pair, Call::Flip, 1, 0)),
+				Err(TransactionValidityError::Invalid(InvalidTransaction::ExhaustsResources))
+			);
+			// Left for a later block.
+			assert_eq!(Nonces::get(&sp_core::Pair::public(&pair).0.into()), Some(1));
+		});
+	}
+
+	#[test]
+	fn executes_blocks() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::from_string("//Alice", None).unwrap();
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let genesis = sp_core::H256::repeat_byte(1);
+			BlockHash::insert(&0, &genesis);
+			let extrinsics = vec![sign(&pair, Call::Flip, 0, 1), sign(&pair, Call::Flip, 1, 1)];
+			let root = super::extrinsics_root(extrinsics.iter().map(Encode::encode).collect());
+			let header = Header::new(1, root, Default::default(), genesis, Default::default());
+
+			Runtime::execute_block(Block { header, extrinsics });
+
+			assert_eq!(Bit::get(), Some(false));
+			assert_eq!(Nonces::get(&sp_core::Pair::public(&pair).0.into()), Some(2));
+		});
+	}
+
+	#[test]
+	fn executes_past_blocks_without_signature_checks() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::from_string("//Alice", None).unwrap();
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			// Signed for what `additional_signed` was then, here another genesis hash.
+			BlockHash::insert(&0, &sp_core::H256::repeat_byte(2));
+			let extrinsics = vec![sign(&pair, Call::Flip, 0, 1)];
+			let genesis = sp_core::H256::repeat_byte(1);
+			BlockHash::insert(&0, &genesis);
+			let root = super::extrinsics_root(extrinsics.iter().map(Encode::encode).collect());
+			let header = Header::new(1, root, Default::default(), genesis, Default::default());
+
+			super::execute(Block { header, extrinsics }, false);
+
+			assert_eq!(Bit::get(), Some(true));
+			assert_eq!(Nonces::get(&sp_core::Pair::public(&pair).0.into()), Some(1));
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "extrinsics root does not match the header")]
+	fn rejects_blocks_with_wrong_extrinsics_root() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::from_string("//Alice", None).unwrap();
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let genesis = sp_core::H256::repeat_byte(1);
+			BlockHash::insert(&0, &genesis);
+			let header =
+				Header::new(1, Default::default(), Default::default(), genesis, Default::default());
+			let extrinsics = vec![sign(&pair, Call::Flip, 0, 1)];
+
+			Runtime::execute_block(Block { header, extrinsics });
+		});
+	}
+
+	#[test]
+	fn builds_blocks_with_extrinsics_root() {
+		let pair: sp_core::sr25519::Pair = sp_core::Pair::from_string("//Alice", None).unwrap();
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let genesis = sp_core::H256::repeat_byte(1);
+			BlockHash::insert(&0, &genesis);
+			let extrinsics = vec![sign(&pair, Call::Flip, 0, 1), sign(&pair, Call::Flip, 1, 1)];
+			Runtime::initialize_block(&Header::new(
+				1,
+				Default::default(),
+				Default::default(),
+				genesis,
+				Default::default(),
+			));
+			for extrinsic in extrinsics.clone() {
+				Runtime::apply_extrinsic(extrinsic).unwrap().unwrap();
+			}
+
+			let header = Runtime::finalize_block();
+
+			assert_eq!(
+				header.extrinsics_root,
+				super::extrinsics_root(extrinsics.iter().map(Encode::encode).collect())
+			);
+			assert!(!ExtrinsicData::contains_key(&0));
+		});
+	}
+
+	#[test]
+	fn overrides_admins_and_authorities() {
+		let (alice, bob) = (sp_core::H256::repeat_byte(1), sp_core::H256::repeat_byte(2));
+		let proposal = multisig::Proposal { call: Call::Pause, approvals: vec![alice] };
+		let mut storage = Storage {
+			top: [
+				(Value::hashed_key().to_vec(), 7u32.encode()),
+				(consensus::GrandpaSetId::hashed_key().to_vec(), 3u64.encode()),
+				(multisig::Proposals::hashed_key_for(&0), proposal.encode()),
+			]
+			.into_iter()
+			.collect(),
+			children_default: Default::default(),
+		};
+		let aura: AuraId =
+			sp_core::sr25519::Pair::from_string("//Alice", None).unwrap().public().into();
+		let grandpa = sp_core::ed25519::Pair::from_string("//Alice", None).unwrap().public().into();
+		let overrides = GenesisOverrides {
+			admins: Some((vec![bob], 1)),
+			authorities: Some(vec![(aura.clone(), grandpa)]),
+		};
+
+		overrides.assimilate_storage(&mut storage).unwrap();
+
+		sp_io::TestExternalities::new(storage).execute_with(|| {
+			assert_eq!(admin::Admins::get(), Some(vec![bob]));
+			assert_eq!(admin::Threshold::get(), Some(1));
+			assert!(!multisig::Proposals::contains_key(&0));
+			assert_eq!(consensus::AuraAuthorities::get(), Some(vec![aura]));
+			assert_eq!(consensus::GrandpaSetId::get(), None);
+			// The rest of the state is kept.
+			assert_eq!(Value::get(), Some(7));
+		});
+		assert!(GenesisOverrides { admins: Some((vec![], 1)), authorities: None }
+			.build_storage()
+			.is_err());
+		assert!(GenesisOverrides { admins: None, authorities: Some(vec![]) }
+			.build_storage()
+			.is_err());
+	}
+
+	#[test]
+	fn overrides_start_over_from_block_zero() {
+		let scheduled =
+			scheduler::Scheduled { id: 4, origin: crate::dispatch::Origin::None, call: Call::Flip };
+		let mut storage = Storage {
+			top: [
+				(Number::hashed_key().to_vec(), 100u32.encode()),
+				(BlockHash::hashed_key_for(&99), sp_core::H256::repeat_byte(9).encode()),
+				(scheduler::Agenda::hashed_key_for(&120), vec![scheduled].encode()),
+				(scheduler::Lookup::hashed_key_for(&4), 120u32.encode()),
+				(upgrade::PendingCode::hashed_key().to_vec(), vec![1u8].encode()),
+				(offchain::LastHeartbeat::hashed_key().to_vec(), 98u32.encode()),
+				(Value::hashed_key().to_vec(), 7u32.encode()),
+			]
+			.into_iter()
+			.collect(),
+			children_default: Default::default(),
+		};
+
+		GenesisOverrides::default().assimilate_storage(&mut storage).unwrap();
+
+		sp_io::TestExternalities::new(storage).execute_with(|| {
+			assert_eq!(Number::get(), Some(0));
+			assert!(!BlockHash::contains_key(&99));
+			assert!(!scheduler::Agenda::contains_key(&120));
+			assert!(!scheduler::Lookup::contains_key(&4));
+			assert_eq!(upgrade::PendingCode::get(), None);
+			assert_eq!(offchain::LastHeartbeat::get(), None);
+			assert_eq!(Value::get(), Some(7));
+		});
+	}
+
+	#[test]
+	fn reports_account_info() {
+		let (alice, bob) = (sp_core::H256::repeat_byte(1), sp_core::H256::repeat_byte(2));
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			admin::Admins::put(&vec![alice]);
+			balances::Balances::insert(&alice, &10);
+			Nonces::insert(&alice, &3);
+
 			assert_eq!(
-				false,
-				sp_io::storage::get(&BIT_KEY).map(|v| bool::decode(&mut &*v)).unwrap().unwrap()
+				Runtime::account_info(alice),
+				AccountInfo { nonce: 3, free: 10, reserved: 0, is_admin: true }
 			);
+			assert_eq!(Runtime::account_info(bob), AccountInfo::default());
 		});
 	}
 }
@@ -0,0 +1,272 @@
+//! V14 metadata, so that generic clients can introspect the chain.
+//!
+//! FRAME chains prefix calls and events with the index of their pallet. Ours all belong to the
+//! `Flipper` pallet, so extrinsics and event records carry the flat [`Call`] and [`Event`] enums
+//! wrapped in a [`crate::extrinsic::RuntimeCall`] and [`crate::events::RuntimeEvent`] with that
+//! pallet's index. Storage items are grouped by their module prefix.
+
+use crate::{
+	admin::{self, Admins, Paused, Threshold},
+	balances::{Balance, Balances},
+	consensus::{AuraAuthorities, GrandpaAuthorities, GrandpaSetId},
+	dispatch::{DispatchError, FLIPPER_PALLET_INDEX},
+	events::{Event, Events},
+	extensions::{self, BlockHash, Nonces},
+	extrinsic::{BasicExtrinsic, EXTRINSIC_FORMAT_VERSION},
+	migrations::LastRuntimeUpgrade,
+	multisig::{NextProposalId, Proposals},
+	offchain::{HeartbeatBlock, LastHeartbeat},
+	rewards::BlockReward,
+	scheduler::{self, Agenda, Lookup, NextScheduledId},
+	storage::{StorageMap, StorageValue},
+	upgrade::{Authorized, PendingCode},
+	Bit, BlockWeight, Call, ExtrinsicData, ExtrinsicIndex, Nonce, Number, Runtime, Value,
+};
+use frame_metadata::{
+	v14::{
+		ExtrinsicMetadata, PalletCallMetadata, PalletConstantMetadata, PalletErrorMetadata,
+		PalletEventMetadata, PalletMetadata, PalletStorageMetadata, RuntimeMetadataV14,
+		SignedExtensionMetadata, StorageEntryMetadata, StorageEntryModifier, StorageEntryType,
+		StorageHasher,
+	},
+	RuntimeMetadataPrefixed,
+};
+use parity_scale_codec::{Compact, Encode};
+use scale_info::{meta_type, StaticTypeInfo};
+use sp_core::H256;
+use sp_runtime::generic::Era;
+use sp_std::prelude::*;
+
+fn name(bytes: &'static [u8]) -> &'static str {
+	core::str::from_utf8(bytes).expect("storage names are ASCII; qed")
+}
+
+fn value<S: StorageValue>() -> StorageEntryMetadata
+where
+	S::Value: StaticTypeInfo,
+{
+	StorageEntryMetadata {
+		name: name(S::NAME),
+		modifier: StorageEntryModifier::Optional,
+		ty: StorageEntryType::Plain(meta_type::<S::Value>()),
+		default: vec![0],
+		docs: vec![],
+	}
+}
+
+fn map<S: StorageMap>() -> StorageEntryMetadata
+where
+	S::Key: StaticTypeInfo,
+	S::Value: StaticTypeInfo,
+{
+	StorageEntryMetadata {
+		name: name(S::NAME),
+		modifier: StorageEntryModifier::Optional,
+		ty: StorageEntryType::Map {
+			hashers: vec![StorageHasher::Blake2_128Concat],
+			key: meta_type::<S::Key>(),
+			value: meta_type::<S::Value>(),
+		},
+		default: vec![0],
+		docs: vec![],
+	}
+}
+
+fn storage(prefix: &'static [u8], entries: Vec<StorageEntryMetadata>) -> PalletStorageMetadata {
+	PalletStorageMetadata { prefix: name(prefix), entries }
+}
+
+fn constant<T: StaticTypeInfo + Encode>(name: &'static str, value: T) -> PalletConstantMetadata {
+	PalletConstantMetadata { name, ty: meta_type::<T>(), value: value.encode(), docs: vec![] }
+}
+
+fn pallet(name: &'static str, index: u8) -> PalletMetadata {
+	PalletMetadata {
+		name,
+		storage: None,
+		calls: None,
+		event: None,
+		constants: vec![],
+		error: None,
+		index,
+	}
+}
+
+/// A signed extension, as standard tooling knows them by `identifier`.
+fn extension<T: StaticTypeInfo, A: StaticTypeInfo>(
+	identifier: &'static str,
+) -> SignedExtensionMetadata {
+	SignedExtensionMetadata {
+		identifier,
+		ty: meta_type::<T>(),
+		additional_signed: meta_type::<A>(),
+	}
+}
+
+pub fn runtime_metadata() -> RuntimeMetadataPrefixed {
+	let pallets = vec![
+		PalletMetadata {
+			storage: Some(storage(
+				b"System",
+				vec![
+					value::<Number>(),
+					map::<BlockHash>(),
+					map::<Nonces>(),
+					value::<ExtrinsicIndex>(),
+					map::<ExtrinsicData>(),
+					value::<BlockWeight>(),
+					value::<Events>(),
+					value::<LastRuntimeUpgrade>(),
+				],
+			)),
+			constants: vec![constant("BlockHashCount", extensions::BLOCK_HASH_COUNT)],
+			..pallet("System", 0)
+		},
+		PalletMetadata {
+			storage: Some(storage(
+				b"Flipper",
+				vec![
+					value::<Bit>(),
+					value::<Value>(),
+					value::<LastHeartbeat>(),
+					value::<HeartbeatBlock>(),
+				],
+			)),
+			calls: Some(PalletCallMetadata { ty: meta_type::<Call>() }),
+			event: Some(PalletEventMetadata { ty: meta_type::<Event>() }),
+			error: Some(PalletErrorMetadata { ty: meta_type::<DispatchError>() }),
+			..pallet("Flipper", FLIPPER_PALLET_INDEX)
+		},
+		PalletMetadata {
+			storage: Some(storage(b"Balances", vec![map::<Balances>()])),
+			..pallet("Balances", 2)
+		},
+		PalletMetadata {
+			storage: Some(storage(
+				b"Admin",
+				vec![value::<Admins>(), value::<Threshold>(), value::<Paused>()],
+			)),
+			constants: vec![constant("KillConfirmations", admin::KILL_CONFIRMATIONS as u32)],
+			..pallet("Admin", 3)
+		},
+		PalletMetadata {
+			storage: Some(storage(
+				b"Multisig",
+				vec![value::<NextProposalId>(), map::<Proposals>()],
+			)),
+			..pallet("Multisig", 4)
+		},
+		PalletMetadata {
+			storage: Some(storage(
+				b"Scheduler",
+				vec![value::<NextScheduledId>(), map::<Agenda>(), map::<Lookup>()],
+			)),
+			constants: vec![
+				constant("MaxScheduledPerBlock", scheduler::MAX_SCHEDULED_PER_BLOCK as u32),
+				constant("MaxScheduledNanos", scheduler::MAX_SCHEDULED_NANOS),
+			],
+			..pallet("Scheduler", 5)
+		},
+		PalletMetadata {
+			storage: Some(storage(b"Upgrade", vec![value::<Authorized>(), value::<PendingCode>()])),
+			..pallet("Upgrade", 6)
+		},
+		PalletMetadata {
+			storage: Some(storage(b"Rewards", vec![value::<BlockReward>()])),
+			..pallet("Rewards", 7)
+		},
+		PalletMetadata {
+			storage: Some(storage(b"Aura", vec![value::<AuraAuthorities>()])),
+			..pallet("Aura", 8)
+		},
+		PalletMetadata {
+			storage: Some(storage(
+				b"Grandpa",
+				vec![value::<GrandpaAuthorities>(), value::<GrandpaSetId>()],
+			)),
+			..pallet("Grandpa", 9)
+		},
+	];
+
+	// In the order `SignedExtra` and `extensions::additional_signed` encode them.
+	let extrinsic = ExtrinsicMetadata {
+		ty: meta_type::<BasicExtrinsic>(),
+		version: EXTRINSIC_FORMAT_VERSION,
+		signed_extensions: vec![
+			extension::<(), u32>("CheckSpecVersion"),
+			extension::<(), u32>("CheckTxVersion"),
+			extension::<(), H256>("CheckGenesis"),
+			extension::<Era, H256>("CheckMortality"),
+			extension::<Compact<Nonce>, ()>("CheckNonce"),
+			extension::<Compact<Balance>, ()>("ChargeTransactionPayment"),
+		],
+	};
+
+	RuntimeMetadataV14::new(pallets, extrinsic, meta_type::<Runtime>()).into()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{events::RuntimeEvent, extensions::SignedExtra, extrinsic::RuntimeCall};
+	use frame_metadata::RuntimeMetadata;
+
+	#[test]
+	fn describes_storage_and_calls() {
+		let metadata = match runtime_metadata().1 {
+			RuntimeMetadata::V14(metadata) => metadata,
+			_ => panic!("not V14"),
+		};
+		let flipper = metadata.pallets.iter().find(|p| p.name == "Flipper").unwrap();
+		let storage = flipper.storage.as_ref().unwrap();
+		assert_eq!(storage.prefix, "Flipper");
+		assert_eq!(
+			storage.entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+			vec!["Bit", "Value", "LastHeartbeat", "HeartbeatBlock"]
+		);
+
+		let call = metadata.types.resolve(flipper.calls.as_ref().unwrap().ty.id()).unwrap();
+		assert_eq!(call.path().ident(), Some("Call".into()));
+
+		let entries = |pallet: &str| {
+			let pallet = metadata.pallets.iter().find(|p| p.name == pallet).unwrap();
+			let storage = pallet.storage.as_ref().unwrap();
+			storage.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>()
+		};
+		assert!(entries("System").contains(&"LastRuntimeUpgrade".to_string()));
+		assert_eq!(entries("Aura"), vec!["Authorities"]);
+		assert_eq!(entries("Grandpa"), vec!["Authorities", "CurrentSetId"]);
+	}
+
+	#[test]
+	fn outer_enums_use_the_pallet_index() {
+		assert_eq!(RuntimeCall::Flipper(Call::Flip).encode()[0], FLIPPER_PALLET_INDEX);
+		let event = RuntimeEvent::Flipper(Event::BatchCompleted);
+		assert_eq!(event.encode()[0], FLIPPER_PALLET_INDEX);
+
+		let metadata = match runtime_metadata().1 {
+			RuntimeMetadata::V14(metadata) => metadata,
+			_ => panic!("not V14"),
+		};
+		let extrinsic = metadata.types.resolve(metadata.extrinsic.ty.id()).unwrap();
+		let call = extrinsic.type_params().iter().find(|p| p.name() == "Call").unwrap();
+		let call = metadata.types.resolve(call.ty().unwrap().id()).unwrap();
+		match call.type_def() {
+			scale_info::TypeDef::Variant(outer) => {
+				assert_eq!(outer.variants().len(), 1);
+				assert_eq!(outer.variants()[0].name(), "Flipper");
+				assert_eq!(outer.variants()[0].index(), FLIPPER_PALLET_INDEX);
+			},
+			_ => panic!("RuntimeCall is an enum"),
+		}
+	}
+
+	#[test]
+	fn signed_extensions_match_signed_extra() {
+		let extra = SignedExtra { era: Era::Immortal, nonce: 1, tip: 2 };
+		assert_eq!(
+			extra.encode(),
+			(Era::Immortal, Compact(1 as Nonce), Compact(2 as Balance)).encode()
+		);
+	}
+}
@@ -0,0 +1,285 @@
+//! Storage migrations.
+//!
+//! The runtime remembers the `spec_version` it last ran with. When `initialize_block` sees a
+//! different version, every entry of [`MIGRATIONS`] introduced after the remembered version runs
+//! once, in order, before the block's extrinsics are applied.
+
+use crate::{
+	admin::{Admins, Threshold},
+	consensus::{self, AuraAuthorities, GrandpaAuthorities},
+	extensions::BlockHash,
+	storage::{self, unhashed, StorageMap, StorageValue},
+	Address, Bit, Value, ADMIN_KEY, EMOJI, VERSION,
+};
+use log::info;
+use sp_core::H256;
+use sp_std::prelude::*;
+
+storage_value! {
+	/// The `spec_version` of the runtime which last executed a block.
+	pub struct LastRuntimeUpgrade(b"System", b"LastRuntimeUpgrade"): u32;
+}
+
+/// Chains which predate [`LastRuntimeUpgrade`] were all running this `spec_version`.
+const UNTRACKED_SPEC_VERSION: u32 = 1;
+
+/// A storage migration, run once when upgrading from a runtime older than `spec_version`.
+pub struct VersionedMigration {
+	/// The `spec_version` which introduced the migration.
+	pub spec_version: u32,
+	pub name: &'static str,
+	pub migrate: fn(),
+}
+
+/// All migrations, ordered by `spec_version`.
+pub const MIGRATIONS: &[VersionedMigration] = &[
+	VersionedMigration { spec_version: 2, name: "v1::prefixed_keys", migrate: v1::migrate },
+	VersionedMigration { spec_version: 3, name: "v2::stored_admins", migrate: v2::migrate },
+	VersionedMigration { spec_version: 4, name: "v3::multisig", migrate: v3::migrate },
+	VersionedMigration { spec_version: 5, name: "v4::genesis_hash", migrate: v4::migrate },
+];
+
+/// Runs any pending migrations if the runtime was upgraded since the last block, returning the
+/// names of those which ran.
+pub fn on_runtime_upgrade() -> Vec<&'static str> {
+	let current = VERSION.spec_version;
+	let last = LastRuntimeUpgrade::get().unwrap_or(UNTRACKED_SPEC_VERSION);
+	if last == current {
+		return Vec::new()
+	}
+
+	info!(target: "migration", "{EMOJI} runtime upgraded from spec_version {last} to {current}");
+	let mut ran = Vec::new();
+	for migration in pending(last, current) {
+		info!(target: "migration", "{EMOJI} running {} ({})", migration.name, migration.spec_version);
+		(migration.migrate)();
+		ran.push(migration.name);
+	}
+	LastRuntimeUpgrade::put(&current);
+	ran
+}
+
+fn pending(last: u32, current: u32) -> impl Iterator<Item = &'static VersionedMigration> {
+	MIGRATIONS
+		.iter()
+		.filter(move |m| m.spec_version > last && m.spec_version <= current)
+}
+
+/// Moves the flipper state from the raw ASCII keys used before the typed storage layer to their
+/// prefixed locations.
+pub mod v1 {
+	use super::*;
+
+	/// Raw key of the flipper bit before v1.
+	pub const LEGACY_BIT_KEY: [u8; 3] = *b"bit";
+	/// Raw key of the adder/multiplier value before v1.
+	pub const LEGACY_VALUE_KEY: [u8; 5] = *b"value";
+
+	/// Moves any legacy data to the new keys, leaving nothing behind.
+	pub fn migrate() {
+		if let Some(bit) = unhashed::take::<bool>(&LEGACY_BIT_KEY) {
+			info!(target: "migration", "{EMOJI} moving legacy bit: {bit}");
+			Bit::put(&bit);
+		}
+		if let Some(value) = unhashed::take::<u32>(&LEGACY_VALUE_KEY) {
+			info!(target: "migration", "{EMOJI} moving legacy value: {value}");
+			Value::put(&value);
+		}
+	}
+}
+
+/// Seeds the on-chain admin set with the key which was hard-coded before admins were stored.
+pub mod v2 {
+	use super::*;
+
+	pub fn migrate() {
+		if !Admins::exists() {
+			info!(target: "migration", "{EMOJI} seeding admins with the legacy admin key");
+			Admins::put(&vec![Address::from(ADMIN_KEY)]);
+		}
+	}
+}
+
+/// Moves to multisig admin calls and stored authorities: a threshold of one keeps the existing
+/// admins in control, the authorities are the ones which used to be hard-coded (Alice), and the
+/// kill confirmations superseded by proposals are dropped.
+pub mod v3 {
+	use super::*;
+	use sp_application_crypto::ByteArray;
+	use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+	use sp_finality_grandpa::AuthorityId as GrandpaId;
+
+	pub const LEGACY_AURA_AUTHORITY: [u8; 32] =
+		hex_literal::hex!("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d");
+	pub const LEGACY_GRANDPA_AUTHORITY: [u8; 32] =
+		hex_literal::hex!("88dc3417d5058ec4b4503e0c12ea1a0a89be200fe98922423d4334014fa6b0ee");
+
+	pub fn migrate() {
+		if !Threshold::exists() {
+			Threshold::put(&1);
+		}
+		if !AuraAuthorities::exists() {
+			info!(target: "migration", "{EMOJI} storing the legacy authorities");
+			let aura = AuraId::from_slice(&LEGACY_AURA_AUTHORITY).expect("32 bytes; qed");
+			let grandpa = GrandpaId::from_slice(&LEGACY_GRANDPA_AUTHORITY).expect("32 bytes; qed");
+			AuraAuthorities::put(&vec![aura]);
+			GrandpaAuthorities::put(&consensus::grandpa_weighted(vec![grandpa]));
+		}
+		unhashed::kill(&storage::storage_prefix(b"Admin", b"KillConfirmations"));
+	}
+}
+
+/// Records the genesis hash which signed extrinsics cover since `spec_version` 5.
+///
+/// New chains record it while initializing block 1, but the runtime cannot look up the genesis
+/// hash of a chain which was past block 1 when it upgraded. Such chains have to build the upgrade
+/// with their genesis hash in the `LEGACY_GENESIS_HASH` environment variable, e.g.
+/// `LEGACY_GENESIS_HASH=0x... cargo build --release`. Without it no signed extrinsic would ever be
+/// valid again, admin ones included, so the migration refuses to run instead: `try-upgrade` reports
+/// the panic before such an upgrade is proposed.
+pub mod v4 {
+	use super::*;
+
+	/// The genesis hash the runtime was built with, as hex.
+	pub const LEGACY_GENESIS_HASH: Option<&str> = option_env!("LEGACY_GENESIS_HASH");
+
+	pub fn migrate() {
+		migrate_with(
+			LEGACY_GENESIS_HASH.map(|hex| parse_hash(hex).expect("checked by build.rs; qed")),
+		)
+	}
+
+	pub fn migrate_with(genesis: Option<H256>) {
+		if BlockHash::contains_key(&0) {
+			return
+		}
+		let genesis = genesis.expect(
+			"the genesis hash of a chain upgrading from before spec_version 5 is unknown; rebuild \
+			 the runtime with LEGACY_GENESIS_HASH set",
+		);
+		info!(target: "migration", "{EMOJI} recording genesis hash {:?}", genesis);
+		BlockHash::insert(&0, &genesis);
+	}
+
+	/// Parses 32 hex encoded bytes, with or without a `0x` prefix.
+	pub fn parse_hash(hex: &str) -> Option<H256> {
+		let hex = hex.strip_prefix("0x").unwrap_or(hex);
+		if hex.len() != 64 || !hex.is_ascii() {
+			return None
+		}
+		let mut hash = H256::zero();
+		for (byte, digits) in hash.0.iter_mut().zip(hex.as_bytes().chunks(2)) {
+			let digits = core::str::from_utf8(digits).ok()?;
+			*byte = u8::from_str_radix(digits, 16).ok()?;
+		}
+		Some(hash)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Header, Runtime};
+	use parity_scale_codec::Encode;
+	use sp_api::runtime_decl_for_Core::Core;
+	use sp_runtime::{traits::Header as _, Storage};
+
+	/// Loads the `genesis.raw.top` section of a raw chain spec, e.g. as written by `export-state`.
+	fn load_snapshot(name: &str) -> sp_io::TestExternalities {
+		let path = format!("{}/res/snapshots/{name}.json", env!("CARGO_MANIFEST_DIR"));
+		let spec: serde_json::Value =
+			serde_json::from_slice(&std::fs::read(&path).expect("snapshot exists")).unwrap();
+		let top = spec["genesis"]["raw"]["top"].as_object().expect("raw chain spec");
+
+		let mut storage = Storage::default();
+		for (key, value) in top {
+			storage.top.insert(
+				sp_core::bytes::from_hex(key).unwrap(),
+				sp_core::bytes::from_hex(value.as_str().unwrap()).unwrap(),
+			);
+		}
+		sp_io::TestExternalities::new(storage)
+	}
+
+	fn header(number: u32) -> Header {
+		Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		)
+	}
+
+	#[test]
+	fn migrations_are_ordered() {
+		assert!(MIGRATIONS.windows(2).all(|w| w[0].spec_version <= w[1].spec_version));
+		assert!(MIGRATIONS.iter().all(|m| m.spec_version <= VERSION.spec_version));
+	}
+
+	#[test]
+	fn upgrades_spec_1_snapshot() {
+		load_snapshot("spec_1").execute_with(|| {
+			assert_eq!(LastRuntimeUpgrade::get(), None);
+
+			Runtime::initialize_block(&header(1));
+
+			assert_eq!(LastRuntimeUpgrade::get(), Some(VERSION.spec_version));
+			assert_eq!(Bit::get(), Some(true));
+			assert_eq!(Value::get(), Some(42));
+			assert!(!unhashed::exists(&v1::LEGACY_BIT_KEY));
+			assert!(!unhashed::exists(&v1::LEGACY_VALUE_KEY));
+			assert_eq!(Admins::get(), Some(vec![Address::from(ADMIN_KEY)]));
+			assert_eq!(Threshold::get(), Some(1));
+			assert_eq!(AuraAuthorities::get().map(|a| a.len()), Some(1));
+			assert_eq!(GrandpaAuthorities::get().map(|a| a.len()), Some(1));
+		});
+	}
+
+	#[test]
+	fn records_genesis_hash_of_upgraded_chains() {
+		let genesis = H256::repeat_byte(7);
+		assert_eq!(v4::parse_hash(&format!("{:?}", genesis)), Some(genesis));
+		assert_eq!(v4::parse_hash(&"07".repeat(32)), Some(genesis));
+		assert_eq!(v4::parse_hash("0x07"), None);
+		assert_eq!(v4::parse_hash(&"zz".repeat(32)), None);
+
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			// A spec 4 chain at block 100, which never recorded its genesis hash.
+			LastRuntimeUpgrade::put(&4);
+			BlockHash::insert(&99, &H256::repeat_byte(9));
+
+			v4::migrate_with(Some(genesis));
+
+			assert_eq!(BlockHash::get(&0), Some(genesis));
+			// A recorded genesis hash is kept.
+			v4::migrate_with(Some(H256::repeat_byte(8)));
+			assert_eq!(BlockHash::get(&0), Some(genesis));
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "LEGACY_GENESIS_HASH")]
+	fn refuses_to_upgrade_without_the_genesis_hash() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			LastRuntimeUpgrade::put(&4);
+			BlockHash::insert(&99, &H256::repeat_byte(9));
+
+			v4::migrate_with(None);
+		});
+	}
+
+	#[test]
+	fn migrations_run_once() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			LastRuntimeUpgrade::put(&VERSION.spec_version);
+			sp_io::storage::set(&v1::LEGACY_BIT_KEY, &true.encode());
+
+			assert!(on_runtime_upgrade().is_empty());
+
+			// Already on the current version, so the legacy key is left alone.
+			assert!(unhashed::exists(&v1::LEGACY_BIT_KEY));
+			assert_eq!(Bit::get(), None);
+		});
+	}
+}
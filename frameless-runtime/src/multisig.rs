@@ -0,0 +1,181 @@
+//! M-of-N approval of admin calls.
+//!
+//! An admin proposes a call with `Call::Propose`, which counts as their approval. Other admins
+//! approve it with `Call::Approve`, and once [`Threshold`] of the current admins have approved, the
+//! call is dispatched with the admin origin and the proposal is removed.
+
+use crate::{
+	admin::{self, Admins, Threshold},
+	dispatch::{self, Origin},
+	events::{deposit_event, Event},
+	storage::{with_transaction, StorageMap, StorageValue},
+	Address, Call, EMOJI,
+};
+use log::info;
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_std::prelude::*;
+
+/// Identifies a proposal.
+pub type ProposalId = u32;
+
+/// An admin call waiting for approvals.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub struct Proposal {
+	pub call: Call,
+	/// The admins which have approved, including the proposer.
+	pub approvals: Vec<Address>,
+}
+
+storage_value! {
+	/// The id of the next proposal.
+	pub struct NextProposalId(b"Multisig", b"NextProposalId"): ProposalId;
+}
+
+storage_map! {
+	/// Proposals which have not reached their threshold yet.
+	pub struct Proposals(b"Multisig", b"Proposals"): map ProposalId => Proposal;
+}
+
+/// Why a proposal or approval was refused.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum MultisigError {
+	/// Only admins may propose and approve.
+	NotAdmin,
+	/// Only admin calls need proposing.
+	NotAdminCall,
+	UnknownProposal,
+	AlreadyApproved,
+	/// Every proposal id has been used.
+	ProposalIdOverflow,
+}
+
+/// Approvals needed for `call`. Killing the chain always needs [`admin::KILL_CONFIRMATIONS`].
+pub fn required_approvals(call: &Call) -> usize {
+	let threshold = Threshold::get().unwrap_or(1) as usize;
+	match call {
+		Call::Kill { .. } => threshold.max(admin::KILL_CONFIRMATIONS),
+		_ => threshold,
+	}
+}
+
+pub fn propose(who: Address, call: Call) -> Result<ProposalId, MultisigError> {
+	if !admin::is_admin(&who) {
+		return Err(MultisigError::NotAdmin)
+	}
+	if !call.is_admin_call() {
+		return Err(MultisigError::NotAdminCall)
+	}
+	let id = NextProposalId::get().unwrap_or(0);
+	NextProposalId::put(&id.checked_add(1).ok_or(MultisigError::ProposalIdOverflow)?);
+	info!(target: "multisig", "{EMOJI} proposal {id}: {:?}", call);
+
+	Proposals::insert(&id, &Proposal { call, approvals: vec![who] });
+	execute_if_approved(id);
+	Ok(id)
+}
+
+pub fn approve(who: Address, id: ProposalId) -> Result<(), MultisigError> {
+	if !admin::is_admin(&who) {
+		return Err(MultisigError::NotAdmin)
+	}
+	Proposals::mutate(&id, |proposal| {
+		let proposal = proposal.as_mut().ok_or(MultisigError::UnknownProposal)?;
+		if proposal.approvals.contains(&who) {
+			return Err(MultisigError::AlreadyApproved)
+		}
+		proposal.approvals.push(who);
+		Ok(())
+	})?;
+	execute_if_approved(id);
+	Ok(())
+}
+
+/// Dispatches the proposal if enough of the *current* admins have approved it.
+fn execute_if_approved(id: ProposalId) {
+	let proposal = match Proposals::get(&id) {
+		Some(proposal) => proposal,
+		None => return,
+	};
+	let admins = Admins::get().unwrap_or_default();
+	let approvals = proposal.approvals.iter().filter(|a| admins.contains(a)).count();
+	let required = required_approvals(&proposal.call);
+	info!(target: "multisig", "{EMOJI} proposal {id} approved by {approvals}/{required}");
+
+	if approvals >= required {
+		Proposals::remove(&id);
+		let result = with_transaction(|| dispatch::dispatch(proposal.call, Origin::Admin));
+		info!(target: "multisig", "{EMOJI} proposal {id} executed: {:?}", result);
+		deposit_event(Event::ProposalExecuted { id, result });
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+
+	fn with_admins(threshold: u32, f: impl FnOnce(Address, Address, Address)) {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let admins = vec![H256::repeat_byte(1), H256::repeat_byte(2), H256::repeat_byte(3)];
+			Admins::put(&admins);
+			Threshold::put(&threshold);
+			f(admins[0], admins[1], admins[2])
+		})
+	}
+
+	#[test]
+	fn dispatches_at_threshold() {
+		with_admins(2, |alice, bob, _| {
+			let id = propose(alice, Call::Pause).unwrap();
+			assert!(!admin::is_paused());
+			assert_eq!(approve(alice, id), Err(MultisigError::AlreadyApproved));
+
+			assert_eq!(approve(bob, id), Ok(()));
+			assert!(admin::is_paused());
+			assert_eq!(Proposals::get(&id), None);
+			assert_eq!(approve(bob, id), Err(MultisigError::UnknownProposal));
+		});
+	}
+
+	#[test]
+	fn only_admins_propose_admin_calls() {
+		with_admins(1, |alice, _, _| {
+			assert_eq!(propose(H256::repeat_byte(9), Call::Pause), Err(MultisigError::NotAdmin));
+			assert_eq!(propose(alice, Call::Flip), Err(MultisigError::NotAdminCall));
+		});
+	}
+
+	#[test]
+	fn proposal_ids_do_not_wrap() {
+		with_admins(2, |alice, _, _| {
+			NextProposalId::put(&ProposalId::MAX);
+			assert_eq!(propose(alice, Call::Pause), Err(MultisigError::ProposalIdOverflow));
+			assert!(!Proposals::contains_key(&ProposalId::MAX));
+		});
+	}
+
+	#[test]
+	fn kill_needs_more_than_one_approval() {
+		with_admins(1, |alice, bob, _| {
+			admin::pause();
+			let id = propose(alice, Call::Kill { password: b"bye".to_vec() }).unwrap();
+			assert!(Proposals::get(&id).is_some());
+
+			approve(bob, id).unwrap();
+			assert_eq!(sp_io::storage::get(sp_storage::well_known_keys::CODE), Some(vec![]));
+		});
+	}
+
+	#[test]
+	fn removed_admins_do_not_count() {
+		with_admins(2, |alice, bob, carol| {
+			let id = propose(alice, Call::Resume).unwrap();
+			Admins::put(&vec![bob, carol]);
+			approve(bob, id).unwrap();
+			assert!(Proposals::get(&id).is_some());
+			approve(carol, id).unwrap();
+			assert_eq!(Proposals::get(&id), None);
+		});
+	}
+}
@@ -0,0 +1,323 @@
+//! The offchain worker, run by the node after importing a block.
+//!
+//! After every block it writes a [`Snapshot`] of the flipper state to the persistent offchain
+//! storage, under [`SNAPSHOT_KEY`]. If a [`Config`] is stored under [`CONFIG_KEY`], e.g. with the
+//! `offchain_localStorageSet` RPC, it also adds 1 to the value every `period` blocks: either with
+//! an unsigned `Call::Heartbeat` signed by the node's Aura authority key, like `pallet_im_online`
+//! heartbeats, or with a `Call::Add(1)` signed by the first sr25519 key of type [`KEY_TYPE`] in
+//! the keystore. The chain takes at most one heartbeat per block.
+
+use crate::{
+	block_number,
+	consensus::AuraAuthorities,
+	extensions::{Nonces, SignedExtra},
+	extrinsic::{signing_payload, BasicExtrinsic},
+	storage::{StorageMap, StorageValue},
+	Address, AsCompact, Bit, BlockNumber, Call, Signature, Value, EMOJI,
+};
+use log::info;
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{
+	crypto::{key_types::AURA, KeyTypeId},
+	offchain::StorageKind,
+	sr25519,
+};
+use sp_runtime::{
+	generic::Era,
+	transaction_validity::{InvalidTransaction, TransactionValidity, ValidTransaction},
+	MultiAddress,
+};
+use sp_std::prelude::*;
+
+/// Where the worker reads its [`Config`] from.
+pub const CONFIG_KEY: &[u8] = b"flipper::ocw::config";
+/// Where the worker writes the latest [`Snapshot`] to.
+pub const SNAPSHOT_KEY: &[u8] = b"flipper::ocw::snapshot";
+/// The keystore key type of the signer of `Call::Add` heartbeats.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"flip");
+/// How many blocks a heartbeat waits in the pool for its sender to author a block.
+const HEARTBEAT_LONGEVITY: BlockNumber = 64;
+
+/// What an authority signs with its Aura key to send a heartbeat.
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize, parity_util_mem::MallocSizeOf))]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub struct Heartbeat {
+	/// The block whose offchain worker sent the heartbeat.
+	pub block_number: BlockNumber,
+	/// The index of the sender in [`AuraAuthorities`].
+	pub authority_index: u32,
+}
+
+storage_value! {
+	/// The `block_number` of the last heartbeat taken, so that it cannot be replayed.
+	pub struct LastHeartbeat(b"Flipper", b"LastHeartbeat"): BlockNumber;
+}
+
+storage_value! {
+	/// The block which took the last heartbeat.
+	pub struct HeartbeatBlock(b"Flipper", b"HeartbeatBlock"): BlockNumber;
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct Config {
+	/// Submit a heartbeat at every block whose number is a multiple of this. 0 disables them.
+	pub period: BlockNumber,
+	/// Whether to sign heartbeats instead of submitting them unsigned.
+	pub signed: bool,
+}
+
+/// The flipper state after a block.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct Snapshot {
+	pub number: BlockNumber,
+	pub bit: bool,
+	pub value: u32,
+}
+
+pub fn offchain_worker(number: BlockNumber) {
+	let snapshot =
+		Snapshot { number, bit: Bit::get().unwrap_or(false), value: Value::get().unwrap_or(0) };
+	sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, SNAPSHOT_KEY, &snapshot.encode());
+
+	let config = match sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, CONFIG_KEY)
+		.and_then(|config| Config::decode(&mut &config[..]).ok())
+	{
+		Some(config) if config.period > 0 => config,
+		_ => return,
+	};
+	if number % config.period != 0 {
+		return
+	}
+
+	let extrinsic = if config.signed {
+		// The heartbeat is validated for the next block.
+		match sign(Call::Add(AsCompact(1)), number + 1) {
+			Some(extrinsic) => extrinsic,
+			None => {
+				info!(target: "offchain", "{EMOJI} no {:?} key to sign the heartbeat", KEY_TYPE);
+				return
+			},
+		}
+	} else {
+		match heartbeat(number) {
+			Some(call) => BasicExtrinsic::new_unsigned(call),
+			None => {
+				info!(target: "offchain", "{EMOJI} no authority key to sign the heartbeat");
+				return
+			},
+		}
+	};
+	match sp_io::offchain::submit_transaction(extrinsic.encode()) {
+		Ok(()) => info!(target: "offchain", "{EMOJI} heartbeat submitted at block {number}"),
+		Err(()) => info!(target: "offchain", "{EMOJI} heartbeat refused at block {number}"),
+	}
+}
+
+/// Signs `call` with an immortal era, as the next extrinsic of the keystore account.
+fn sign(call: Call, now: BlockNumber) -> Option<BasicExtrinsic> {
+	let public = sp_io::crypto::sr25519_public_keys(KEY_TYPE).into_iter().next()?;
+	let who = Address::from(public.0);
+	let extra = SignedExtra { era: Era::Immortal, nonce: Nonces::get(&who).unwrap_or(0), tip: 0 };
+	let payload = signing_payload(&call, &extra, now).ok()?;
+	let signature = sp_io::crypto::sr25519_sign(KEY_TYPE, &public, &payload)?;
+	Some(BasicExtrinsic::new_signed(
+		call,
+		(MultiAddress::Id(who), Signature::Sr25519(signature), extra),
+	))
+}
+
+fn public(authority: &AuraId) -> &sr25519::Public {
+	authority.as_ref()
+}
+
+/// A heartbeat from block `number`, signed by the first Aura key in the keystore which belongs to
+/// an authority.
+fn heartbeat(number: BlockNumber) -> Option<Call> {
+	let authorities = AuraAuthorities::get().unwrap_or_default();
+	let (authority_index, key) =
+		sp_io::crypto::sr25519_public_keys(AURA).into_iter().find_map(|key| {
+			let index = authorities.iter().position(|authority| *public(authority) == key)?;
+			Some((index as u32, key))
+		})?;
+	let heartbeat = Heartbeat { block_number: number, authority_index };
+	let signature = sp_io::crypto::sr25519_sign(AURA, &key, &heartbeat.encode())?;
+	Some(Call::Heartbeat { heartbeat, signature })
+}
+
+/// Checks that `heartbeat` was signed by the authority it names, is recent, and that no heartbeat
+/// was taken in block `now` yet.
+pub fn validate_heartbeat(
+	heartbeat: &Heartbeat,
+	signature: &sr25519::Signature,
+	now: BlockNumber,
+) -> TransactionValidity {
+	if heartbeat.block_number >= now {
+		return Err(InvalidTransaction::Future.into())
+	}
+	let expired = heartbeat.block_number.saturating_add(HEARTBEAT_LONGEVITY) < now;
+	let replayed = LastHeartbeat::get().map_or(false, |last| heartbeat.block_number <= last);
+	if expired || replayed || HeartbeatBlock::get() == Some(now) {
+		return Err(InvalidTransaction::Stale.into())
+	}
+
+	let authorities = AuraAuthorities::get().unwrap_or_default();
+	let authority = authorities
+		.get(heartbeat.authority_index as usize)
+		.ok_or(InvalidTransaction::BadProof)?;
+	if !sp_io::crypto::sr25519_verify(signature, &heartbeat.encode(), public(authority)) {
+		return Err(InvalidTransaction::BadProof.into())
+	}
+
+	Ok(ValidTransaction {
+		provides: vec![(b"heartbeat", heartbeat.block_number).encode()],
+		longevity: u64::from(heartbeat.block_number) + u64::from(HEARTBEAT_LONGEVITY) + 1 -
+			u64::from(now),
+		// Only the sender's own node includes it.
+		propagate: false,
+		..Default::default()
+	})
+}
+
+/// Called when dispatching a heartbeat, which has been validated.
+pub fn note_heartbeat(heartbeat: &Heartbeat) {
+	info!(target: "offchain", "{EMOJI} heartbeat of authority {}", heartbeat.authority_index);
+	LastHeartbeat::put(&heartbeat.block_number);
+	HeartbeatBlock::put(&block_number());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		dispatch::{dispatch, Origin},
+		extensions::BlockHash,
+		CurrentHeader, DispatchError, Header,
+	};
+	use sp_core::{
+		offchain::{testing, OffchainDbExt, OffchainWorkerExt, TransactionPoolExt},
+		Pair, H256,
+	};
+	use sp_keystore::{testing::KeyStore, KeystoreExt, SyncCryptoStore};
+	use sp_runtime::traits::Header as _;
+	use std::sync::Arc;
+
+	/// Runs `f` with offchain extensions, passing it what has been submitted so far.
+	fn with_offchain(keystore: KeyStore, f: impl FnOnce(&dyn Fn() -> Vec<BasicExtrinsic>)) {
+		let (offchain, _) = testing::TestOffchainExt::new();
+		let (pool, pool_state) = testing::TestTransactionPoolExt::new();
+		let mut ext = sp_io::TestExternalities::new_empty();
+		ext.register_extension(OffchainWorkerExt::new(offchain.clone()));
+		ext.register_extension(OffchainDbExt::new(offchain));
+		ext.register_extension(TransactionPoolExt::new(pool));
+		ext.register_extension(KeystoreExt(Arc::new(keystore)));
+
+		let submitted = || {
+			let pool = pool_state.read();
+			pool.transactions
+				.iter()
+				.map(|tx| BasicExtrinsic::decode(&mut &tx[..]).unwrap())
+				.collect()
+		};
+		ext.execute_with(|| {
+			BlockHash::insert(&0, &H256::repeat_byte(1));
+			f(&submitted)
+		})
+	}
+
+	fn configure(period: BlockNumber, signed: bool) {
+		let config = Config { period, signed }.encode();
+		sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, CONFIG_KEY, &config);
+	}
+
+	fn snapshot() -> Option<Snapshot> {
+		sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, SNAPSHOT_KEY)
+			.map(|snapshot| Snapshot::decode(&mut &snapshot[..]).unwrap())
+	}
+
+	#[test]
+	fn snapshots_and_submits_unsigned_heartbeat() {
+		let keystore = KeyStore::new();
+		let public = keystore.sr25519_generate_new(AURA, Some("//Alice")).unwrap();
+		with_offchain(keystore, |submitted| {
+			Value::put(&7);
+			offchain_worker(3);
+			assert_eq!(snapshot(), Some(Snapshot { number: 3, bit: false, value: 7 }));
+
+			configure(2, false);
+			offchain_worker(3);
+			assert!(submitted().is_empty());
+			// Not an authority yet.
+			offchain_worker(4);
+			assert_eq!(snapshot().map(|s| s.number), Some(4));
+			assert!(submitted().is_empty());
+
+			let bob = sp_core::sr25519::Pair::from_string("//Bob", None).unwrap().public();
+			AuraAuthorities::put(&vec![bob.into(), public.into()]);
+			offchain_worker(6);
+			let submitted = submitted();
+			assert_eq!(submitted.len(), 1);
+			match &submitted[0].call {
+				Call::Heartbeat { heartbeat, signature } => {
+					assert_eq!(*heartbeat, Heartbeat { block_number: 6, authority_index: 1 });
+					assert!(validate_heartbeat(heartbeat, signature, 7).is_ok());
+				},
+				call => panic!("not a heartbeat: {:?}", call),
+			}
+		});
+	}
+
+	#[test]
+	fn takes_one_heartbeat_per_block() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			CurrentHeader::put(&Header::new(
+				5,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			));
+			// Dispatching does not check the signature, `validate_unsigned` does.
+			let signature = || sr25519::Signature::from_raw([0; 64]);
+			let heartbeat = Heartbeat { block_number: 3, authority_index: 0 };
+			let call = Call::Heartbeat { heartbeat: heartbeat.clone(), signature: signature() };
+
+			assert_eq!(
+				dispatch(call.clone(), Origin::Signed(H256::zero())),
+				Err(DispatchError::BadOrigin)
+			);
+			assert_eq!(dispatch(call, Origin::None), Ok(()));
+			assert_eq!(Value::get(), Some(1));
+			assert_eq!((LastHeartbeat::get(), HeartbeatBlock::get()), (Some(3), Some(5)));
+
+			let next = Heartbeat { block_number: 4, ..heartbeat };
+			assert_eq!(
+				validate_heartbeat(&next, &signature(), 5),
+				Err(InvalidTransaction::Stale.into())
+			);
+			// Fine for the next block, if it were signed.
+			assert_eq!(
+				validate_heartbeat(&next, &signature(), 6),
+				Err(InvalidTransaction::BadProof.into())
+			);
+		});
+	}
+
+	#[test]
+	fn signs_heartbeat_with_keystore_key() {
+		let keystore = KeyStore::new();
+		let public = keystore.sr25519_generate_new(KEY_TYPE, Some("//Alice")).unwrap();
+		with_offchain(keystore, |submitted| {
+			configure(5, true);
+			offchain_worker(5);
+
+			let submitted = submitted();
+			assert_eq!(submitted.len(), 1);
+			let checked = submitted[0].clone().check(6).unwrap();
+			assert_eq!(checked.signed.map(|(who, _)| who), Some(Address::from(public.0)));
+		});
+	}
+}
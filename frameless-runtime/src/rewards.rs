@@ -0,0 +1,108 @@
+//! Block rewards.
+//!
+//! The author of every block gets [`BlockReward`] minted into their balance. The author is the
+//! Aura authority of the slot in the pre-runtime digest of the header, which `initialize_block`
+//! notes, so that `finalize_block` can pay it once the block's extrinsics have been applied.
+
+use crate::{
+	balances,
+	consensus::AuraAuthorities,
+	events::{deposit_event, Event},
+	storage::StorageValue,
+	Address, Balance, EMOJI,
+};
+use log::info;
+use sp_consensus_aura::{sr25519::AuthorityId as AuraId, Slot, AURA_ENGINE_ID};
+use sp_runtime::Digest;
+
+storage_value! {
+	/// Minted into the balance of the author of every block.
+	pub struct BlockReward(b"Rewards", b"BlockReward"): Balance;
+}
+
+storage_value! {
+	/// The author of the block being built, between `initialize_block` and `finalize_block`.
+	pub struct Author(b"Rewards", b"Author"): Address;
+}
+
+/// The authority of the Aura slot in `digest`, among the authorities of the parent block.
+pub fn find_author(digest: &Digest) -> Option<Address> {
+	let slot = digest
+		.logs()
+		.iter()
+		.find_map(|item| item.pre_runtime_try_to::<Slot>(&AURA_ENGINE_ID))?;
+	let authorities = AuraAuthorities::get().unwrap_or_default();
+	if authorities.is_empty() {
+		return None
+	}
+	Some(address(&authorities[(*slot % authorities.len() as u64) as usize]))
+}
+
+/// The account of the sr25519 key of an authority.
+fn address(authority: &AuraId) -> Address {
+	let key: &[u8] = authority.as_ref();
+	Address::from_slice(key)
+}
+
+/// Called from `initialize_block`.
+pub fn note_author(digest: &Digest) {
+	match find_author(digest) {
+		Some(author) => Author::put(&author),
+		None => Author::kill(),
+	}
+}
+
+/// Called from `finalize_block`.
+pub fn reward_author() {
+	if let (Some(who), Some(amount)) = (Author::take(), BlockReward::get()) {
+		info!(target: "rewards", "{EMOJI} rewarding {:?} with {amount}", who);
+		balances::mint(&who, amount);
+		deposit_event(Event::AuthorRewarded { who, amount });
+	}
+}
+
+pub fn set_block_reward(reward: Balance) {
+	info!(target: "rewards", "{EMOJI} new block reward: {reward}");
+	BlockReward::put(&reward);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::events::{EventRecord, Events};
+	use parity_scale_codec::Encode;
+	use sp_core::Pair;
+	use sp_runtime::DigestItem;
+
+	#[test]
+	fn rewards_the_author_of_the_slot() {
+		let authorities: Vec<AuraId> = ["//Alice", "//Bob"]
+			.iter()
+			.map(|suri| sp_core::sr25519::Pair::from_string(suri, None).unwrap().public().into())
+			.collect();
+		let bob = address(&authorities[1]);
+		let digest =
+			Digest { logs: vec![DigestItem::PreRuntime(AURA_ENGINE_ID, Slot::from(7).encode())] };
+
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			assert_eq!(find_author(&digest), None);
+			AuraAuthorities::put(&authorities);
+			assert_eq!(find_author(&Digest::default()), None);
+			assert_eq!(find_author(&digest), Some(bob));
+
+			note_author(&digest);
+			reward_author();
+			assert_eq!(balances::free_balance(&bob), 0);
+
+			set_block_reward(5);
+			note_author(&digest);
+			reward_author();
+			assert_eq!(balances::free_balance(&bob), 5);
+			assert_eq!(Author::get(), None);
+			assert_eq!(
+				Events::get().unwrap().pop().map(EventRecord::into_event),
+				Some(Event::AuthorRewarded { who: bob, amount: 5 })
+			);
+		});
+	}
+}
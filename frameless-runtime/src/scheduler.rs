@@ -0,0 +1,270 @@
+//! Calls scheduled for a future block.
+//!
+//! `Call::Schedule` stores a call in the agenda of its target block, to be dispatched on behalf of
+//! the scheduler at the start of that block, from `initialize_block`. An agenda takes at most
+//! [`MAX_SCHEDULED_PER_BLOCK`] calls weighing at most [`MAX_SCHEDULED_NANOS`] in total, so that it
+//! fits in its block. Should it not, e.g. because a runtime upgrade changed the weights, the calls
+//! which do not fit are postponed to the next block.
+
+use crate::{
+	admin,
+	dispatch::{self, DispatchError, DispatchResult, Origin},
+	events::{deposit_event, Event},
+	storage::{with_transaction, StorageMap, StorageValue},
+	weights::{self, Weight},
+	BlockNumber, Call, EMOJI,
+};
+use log::info;
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_std::prelude::*;
+
+/// The most calls the agenda of a block takes.
+pub const MAX_SCHEDULED_PER_BLOCK: usize = 50;
+/// The time the agenda of a block may take: part of what `MAXIMUM_BLOCK_NANOS` leaves for
+/// `initialize_block` and `finalize_block`.
+pub const MAX_SCHEDULED_NANOS: u64 = weights::MAXIMUM_BLOCK_NANOS / 5;
+/// What dispatching a scheduled call costs on top of the call: removing its lookup entry and
+/// depositing the event. An estimate, as this is not benchmarked.
+const SCHEDULED_BASE: Weight = Weight { nanos: 10_000, reads: 0, writes: 2 };
+
+/// Identifies a scheduled call.
+pub type ScheduledId = u32;
+
+/// A call waiting in an agenda.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub struct Scheduled {
+	pub id: ScheduledId,
+	pub origin: Origin,
+	pub call: Call,
+}
+
+/// Why scheduling or cancelling was refused.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum SchedulerError {
+	/// Calls can only be scheduled for future blocks.
+	InThePast,
+	UnknownId,
+	/// The agenda of the block has no room left for the call, by count or by weight.
+	AgendaFull,
+	/// Every scheduled call id has been used.
+	ScheduledIdOverflow,
+}
+
+storage_value! {
+	/// The id of the next scheduled call.
+	pub struct NextScheduledId(b"Scheduler", b"NextId"): ScheduledId;
+}
+
+storage_map! {
+	/// The calls to dispatch at the start of a block, in order.
+	pub struct Agenda(b"Scheduler", b"Agenda"): map BlockNumber => Vec<Scheduled>;
+}
+
+storage_map! {
+	/// The block whose agenda holds a scheduled call.
+	pub struct Lookup(b"Scheduler", b"Lookup"): map ScheduledId => BlockNumber;
+}
+
+pub fn schedule(
+	origin: Origin,
+	when: BlockNumber,
+	call: Call,
+	now: BlockNumber,
+) -> Result<ScheduledId, SchedulerError> {
+	if when <= now {
+		return Err(SchedulerError::InThePast)
+	}
+	let mut agenda = Agenda::get(&when).unwrap_or_default();
+	let agenda_weight = agenda.iter().map(|s| weight(&s.call)).fold(0, u64::saturating_add);
+	if agenda.len() >= MAX_SCHEDULED_PER_BLOCK ||
+		agenda_weight.saturating_add(weight(&call)) > MAX_SCHEDULED_NANOS
+	{
+		return Err(SchedulerError::AgendaFull)
+	}
+	let id = NextScheduledId::get().unwrap_or(0);
+	NextScheduledId::put(&id.checked_add(1).ok_or(SchedulerError::ScheduledIdOverflow)?);
+	info!(target: "scheduler", "{EMOJI} scheduled {id} for block {when}: {:?}", call);
+
+	agenda.push(Scheduled { id, origin, call });
+	Agenda::insert(&when, &agenda);
+	Lookup::insert(&id, &when);
+	deposit_event(Event::Scheduled { id, when });
+	Ok(id)
+}
+
+/// Cancels a scheduled call. Only whoever scheduled it, or the admins, may do so.
+pub fn cancel(origin: Origin, id: ScheduledId) -> DispatchResult {
+	let when = Lookup::get(&id).ok_or(SchedulerError::UnknownId)?;
+	Agenda::mutate(&when, |agenda| -> DispatchResult {
+		let items = agenda.as_mut().ok_or(SchedulerError::UnknownId)?;
+		let position = items.iter().position(|s| s.id == id).ok_or(SchedulerError::UnknownId)?;
+		if origin != Origin::Admin && origin != items[position].origin {
+			return Err(DispatchError::BadOrigin)
+		}
+		items.remove(position);
+		if items.is_empty() {
+			*agenda = None;
+		}
+		Ok(())
+	})?;
+	Lookup::remove(&id);
+	info!(target: "scheduler", "{EMOJI} cancelled {id}");
+	deposit_event(Event::ScheduleCancelled { id });
+	Ok(())
+}
+
+/// Dispatches the agenda of block `now`. Called from `initialize_block`.
+pub fn on_initialize(now: BlockNumber) {
+	let mut agenda = match Agenda::take(&now) {
+		Some(agenda) => agenda,
+		None => return,
+	};
+	// At least one call goes through, so that no call can hold up the agenda forever.
+	let mut used = 0u64;
+	let fits = agenda
+		.iter()
+		.take_while(|scheduled| {
+			let fits =
+				used == 0 || used.saturating_add(weight(&scheduled.call)) <= MAX_SCHEDULED_NANOS;
+			used = used.saturating_add(weight(&scheduled.call));
+			fits
+		})
+		.count();
+	if fits < agenda.len() {
+		let postponed = agenda.split_off(fits);
+		info!(
+			target: "scheduler",
+			"{EMOJI} postponing {} calls to block {}", postponed.len(), now + 1
+		);
+		for scheduled in &postponed {
+			Lookup::insert(&scheduled.id, &(now + 1));
+		}
+		Agenda::mutate(&(now + 1), |next| {
+			let next = next.get_or_insert_with(Vec::new);
+			// Ahead of the calls which were scheduled for the next block in the first place.
+			*next = postponed.into_iter().chain(next.drain(..)).collect();
+		});
+	}
+
+	for Scheduled { id, origin, call } in agenda {
+		Lookup::remove(&id);
+		let result = if admin::is_paused() && !call.is_allowed_while_paused(&origin) {
+			Err(DispatchError::CallFiltered)
+		} else {
+			with_transaction(|| dispatch::dispatch(call, origin))
+		};
+		info!(target: "scheduler", "{EMOJI} dispatched {id}: {:?}", result);
+		deposit_event(Event::ScheduledDispatched { id, result });
+	}
+}
+
+/// The time dispatching `call` from an agenda takes.
+fn weight(call: &Call) -> u64 {
+	call.weight().saturating_add(SCHEDULED_BASE).total_nanos()
+}
+
+/// Every scheduled call along with the block it is scheduled for, in block order.
+pub fn pending() -> Vec<(BlockNumber, Scheduled)> {
+	let mut pending = Agenda::iter()
+		.flat_map(|(when, agenda)| agenda.into_iter().map(move |s| (when, s)))
+		.collect::<Vec<_>>();
+	// Map iteration follows the hashed keys, not the block numbers.
+	pending.sort_by_key(|(when, _)| *when);
+	pending
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{AsCompact, Value};
+	use sp_core::H256;
+
+	fn alice() -> Origin {
+		Origin::Signed(H256::repeat_byte(1))
+	}
+
+	/// One of the heaviest calls.
+	fn upgrade() -> Call {
+		Call::ApplyAuthorizedUpgrade { code: vec![] }
+	}
+
+	#[test]
+	fn dispatches_at_target_block() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			assert_eq!(schedule(alice(), 1, Call::Flip, 1), Err(SchedulerError::InThePast));
+			let add = schedule(alice(), 3, Call::Add(AsCompact(2)), 1).unwrap();
+			let multiply = schedule(alice(), 3, Call::Multiply(AsCompact(5)), 1).unwrap();
+			assert_eq!(pending().len(), 2);
+
+			on_initialize(2);
+			assert_eq!(Value::get(), None);
+
+			on_initialize(3);
+			assert_eq!(Value::get(), Some(10));
+			assert!(pending().is_empty());
+			assert_eq!(Lookup::get(&add), None);
+			assert_eq!(Lookup::get(&multiply), None);
+		});
+	}
+
+	#[test]
+	fn only_scheduler_cancels() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let id = schedule(alice(), 3, Call::Flip, 1).unwrap();
+			let bob = Origin::Signed(H256::repeat_byte(2));
+
+			assert_eq!(cancel(bob, id), Err(DispatchError::BadOrigin));
+			assert_eq!(cancel(alice(), id), Ok(()));
+			assert_eq!(Agenda::get(&3), None);
+			assert_eq!(
+				cancel(alice(), id),
+				Err(DispatchError::Scheduler(SchedulerError::UnknownId))
+			);
+		});
+	}
+
+	#[test]
+	fn refuses_full_agendas() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			for _ in 0..MAX_SCHEDULED_PER_BLOCK {
+				schedule(alice(), 2, Call::Flip, 1).unwrap();
+			}
+			assert_eq!(schedule(alice(), 2, Call::Flip, 1), Err(SchedulerError::AgendaFull));
+
+			// Too heavy for an agenda of its own.
+			let upgrades = (MAX_SCHEDULED_NANOS / weight(&upgrade()) + 1) as usize;
+			let call = Call::Batch(vec![upgrade(); upgrades]);
+			assert_eq!(schedule(alice(), 3, call, 1), Err(SchedulerError::AgendaFull));
+
+			NextScheduledId::put(&ScheduledId::MAX);
+			assert_eq!(
+				schedule(alice(), 3, Call::Flip, 1),
+				Err(SchedulerError::ScheduledIdOverflow)
+			);
+			assert_eq!(Agenda::get(&3), None);
+		});
+	}
+
+	#[test]
+	fn postpones_what_does_not_fit() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			// E.g. scheduled before an upgrade made the calls heavier.
+			let fits = (MAX_SCHEDULED_NANOS / weight(&upgrade())) as usize;
+			let agenda = (0..fits as ScheduledId + 2)
+				.map(|id| Scheduled { id, origin: Origin::None, call: upgrade() })
+				.collect::<Vec<_>>();
+			Agenda::insert(&2, &agenda);
+			NextScheduledId::put(&(fits as ScheduledId + 2));
+			let last = schedule(alice(), 3, Call::Flip, 1).unwrap();
+
+			on_initialize(2);
+			let next = Agenda::get(&3).unwrap();
+			assert_eq!(
+				next.iter().map(|s| s.id).collect::<Vec<_>>(),
+				vec![fits as ScheduledId, fits as ScheduledId + 1, last]
+			);
+			assert_eq!(Lookup::get(&(fits as ScheduledId)), Some(3));
+		});
+	}
+}
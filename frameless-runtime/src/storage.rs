@@ -0,0 +1,349 @@
+//! A small typed storage layer, loosely modelled on FRAME's `StorageValue` and `StorageMap`.
+//!
+//! Every item lives under `twox128(module) ++ twox128(name)`. Map entries append
+//! `blake2_128(key) ++ key` to that prefix, so all entries of a map share a prefix (and can be
+//! iterated) while the original key can still be recovered from the raw storage key.
+
+use parity_scale_codec::{Decode, Encode, FullCodec};
+use sp_std::{marker::PhantomData, prelude::*};
+
+/// Length of the `twox128(module) ++ twox128(name)` prefix.
+pub const PREFIX_LEN: usize = 32;
+/// Length of the `blake2_128` hash which precedes the key in a map entry.
+pub const HASH_LEN: usize = 16;
+
+/// `twox128(module) ++ twox128(name)`.
+pub fn storage_prefix(module: &[u8], name: &[u8]) -> [u8; PREFIX_LEN] {
+	let mut prefix = [0u8; PREFIX_LEN];
+	prefix[..16].copy_from_slice(&sp_io::hashing::twox_128(module));
+	prefix[16..].copy_from_slice(&sp_io::hashing::twox_128(name));
+	prefix
+}
+
+/// `blake2_128(key) ++ key`.
+pub fn blake2_128_concat(key: &[u8]) -> Vec<u8> {
+	let mut hashed = sp_io::hashing::blake2_128(key).to_vec();
+	hashed.extend_from_slice(key);
+	hashed
+}
+
+/// Counts storage accesses for the benchmarks, see `benchmarking`.
+#[cfg(all(feature = "std", feature = "runtime-benchmarks"))]
+pub mod tracking {
+	use std::cell::Cell;
+
+	thread_local! {
+		static COUNTS: Cell<(u32, u32)> = Cell::new((0, 0));
+	}
+
+	pub fn note_read() {
+		COUNTS.with(|counts| {
+			let (reads, writes) = counts.get();
+			counts.set((reads + 1, writes));
+		})
+	}
+
+	pub fn note_write() {
+		COUNTS.with(|counts| {
+			let (reads, writes) = counts.get();
+			counts.set((reads, writes + 1));
+		})
+	}
+
+	/// The reads and writes noted since the last call.
+	pub fn take() -> (u32, u32) {
+		COUNTS.with(|counts| counts.replace((0, 0)))
+	}
+}
+
+#[cfg(not(all(feature = "std", feature = "runtime-benchmarks")))]
+pub mod tracking {
+	pub fn note_read() {}
+	pub fn note_write() {}
+}
+
+/// Access to raw, un-prefixed keys, e.g. `well_known_keys` or the pre-v1 flipper keys.
+pub mod unhashed {
+	use super::*;
+
+	/// Read and decode the value at `key`, returning `None` if absent or undecodable.
+	pub fn get<T: Decode>(key: &[u8]) -> Option<T> {
+		tracking::note_read();
+		sp_io::storage::get(key).and_then(|v| T::decode(&mut &*v).ok())
+	}
+
+	/// Encode and write `value` at `key`.
+	pub fn put<T: Encode + ?Sized>(key: &[u8], value: &T) {
+		tracking::note_write();
+		value.using_encoded(|v| sp_io::storage::set(key, v))
+	}
+
+	/// Remove the value at `key`.
+	pub fn kill(key: &[u8]) {
+		tracking::note_write();
+		sp_io::storage::clear(key)
+	}
+
+	/// Read, decode and remove the value at `key`.
+	pub fn take<T: Decode>(key: &[u8]) -> Option<T> {
+		let value = get(key);
+		kill(key);
+		value
+	}
+
+	/// Whether anything is stored at `key`.
+	pub fn exists(key: &[u8]) -> bool {
+		tracking::note_read();
+		sp_io::storage::exists(key)
+	}
+}
+
+/// A single typed value. Implement via [`storage_value!`].
+pub trait StorageValue {
+	type Value: FullCodec;
+	const MODULE: &'static [u8];
+	const NAME: &'static [u8];
+
+	fn hashed_key() -> [u8; PREFIX_LEN] {
+		storage_prefix(Self::MODULE, Self::NAME)
+	}
+
+	fn get() -> Option<Self::Value> {
+		unhashed::get(&Self::hashed_key())
+	}
+
+	fn exists() -> bool {
+		unhashed::exists(&Self::hashed_key())
+	}
+
+	fn put(value: &Self::Value) {
+		unhashed::put(&Self::hashed_key(), value)
+	}
+
+	fn kill() {
+		unhashed::kill(&Self::hashed_key())
+	}
+
+	fn take() -> Option<Self::Value> {
+		unhashed::take(&Self::hashed_key())
+	}
+
+	/// Mutate the value in place; `None` in and out means absent.
+	fn mutate<R>(f: impl FnOnce(&mut Option<Self::Value>) -> R) -> R {
+		let mut value = Self::get();
+		let result = f(&mut value);
+		match value {
+			Some(ref v) => Self::put(v),
+			None => Self::kill(),
+		}
+		result
+	}
+}
+
+/// A typed map whose entries are keyed by `blake2_128_concat(key)`. Implement via
+/// [`storage_map!`].
+pub trait StorageMap {
+	type Key: FullCodec;
+	type Value: FullCodec;
+	const MODULE: &'static [u8];
+	const NAME: &'static [u8];
+
+	fn prefix() -> [u8; PREFIX_LEN] {
+		storage_prefix(Self::MODULE, Self::NAME)
+	}
+
+	fn hashed_key_for(key: &Self::Key) -> Vec<u8> {
+		let mut hashed = Self::prefix().to_vec();
+		hashed.extend(key.using_encoded(blake2_128_concat));
+		hashed
+	}
+
+	fn get(key: &Self::Key) -> Option<Self::Value> {
+		unhashed::get(&Self::hashed_key_for(key))
+	}
+
+	fn contains_key(key: &Self::Key) -> bool {
+		unhashed::exists(&Self::hashed_key_for(key))
+	}
+
+	fn insert(key: &Self::Key, value: &Self::Value) {
+		unhashed::put(&Self::hashed_key_for(key), value)
+	}
+
+	fn remove(key: &Self::Key) {
+		unhashed::kill(&Self::hashed_key_for(key))
+	}
+
+	fn take(key: &Self::Key) -> Option<Self::Value> {
+		unhashed::take(&Self::hashed_key_for(key))
+	}
+
+	/// Mutate the entry in place; `None` in and out means absent.
+	fn mutate<R>(key: &Self::Key, f: impl FnOnce(&mut Option<Self::Value>) -> R) -> R {
+		let hashed = Self::hashed_key_for(key);
+		let mut value = unhashed::get(&hashed);
+		let result = f(&mut value);
+		match value {
+			Some(ref v) => unhashed::put(&hashed, v),
+			None => unhashed::kill(&hashed),
+		}
+		result
+	}
+
+	/// Iterate over all entries, in storage key order.
+	fn iter() -> PrefixIterator<Self::Key, Self::Value> {
+		PrefixIterator::new(Self::prefix().to_vec())
+	}
+
+	/// Remove all entries.
+	fn clear() {
+		tracking::note_write();
+		let _ = sp_io::storage::clear_prefix(&Self::prefix(), None);
+	}
+}
+
+/// Iterates over the entries of a [`StorageMap`] by walking `sp_io::storage::next_key`.
+pub struct PrefixIterator<K, V> {
+	prefix: Vec<u8>,
+	previous_key: Vec<u8>,
+	_marker: PhantomData<(K, V)>,
+}
+
+impl<K, V> PrefixIterator<K, V> {
+	fn new(prefix: Vec<u8>) -> Self {
+		Self { previous_key: prefix.clone(), prefix, _marker: PhantomData }
+	}
+}
+
+impl<K: Decode, V: Decode> Iterator for PrefixIterator<K, V> {
+	type Item = (K, V);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			tracking::note_read();
+			let key = sp_io::storage::next_key(&self.previous_key)
+				.filter(|key| key.starts_with(&self.prefix))?;
+			self.previous_key = key;
+			// Skip over anything we cannot make sense of rather than ending the iteration early.
+			let decoded = self
+				.previous_key
+				.get(self.prefix.len() + HASH_LEN..)
+				.and_then(|mut raw_key| K::decode(&mut raw_key).ok())
+				.zip(unhashed::get::<V>(&self.previous_key));
+			if decoded.is_some() {
+				return decoded
+			}
+		}
+	}
+}
+
+/// Runs `f` in a storage transaction, keeping its writes only if it returns `Ok`.
+pub fn with_transaction<R, E>(f: impl FnOnce() -> Result<R, E>) -> Result<R, E> {
+	sp_io::storage::start_transaction();
+	let result = f();
+	match result {
+		Ok(_) => sp_io::storage::commit_transaction(),
+		Err(_) => sp_io::storage::rollback_transaction(),
+	}
+	result
+}
+
+/// Declares a unit struct implementing [`StorageValue`]:
+/// `storage_value! { pub struct Bit(b"Flipper", b"Bit"): bool; }`
+macro_rules! storage_value {
+	($(#[$attr:meta])* $vis:vis struct $name:ident($module:literal, $item:literal): $value:ty;) => {
+		$(#[$attr])*
+		$vis struct $name;
+
+		impl $crate::storage::StorageValue for $name {
+			type Value = $value;
+			const MODULE: &'static [u8] = $module;
+			const NAME: &'static [u8] = $item;
+		}
+	};
+}
+
+/// Declares a unit struct implementing [`StorageMap`]:
+/// `storage_map! { pub struct Nonces(b"System", b"Nonces"): map Address => u32; }`
+macro_rules! storage_map {
+	(
+		$(#[$attr:meta])*
+		$vis:vis struct $name:ident($module:literal, $item:literal): map $key:ty => $value:ty;
+	) => {
+		$(#[$attr])*
+		$vis struct $name;
+
+		impl $crate::storage::StorageMap for $name {
+			type Key = $key;
+			type Value = $value;
+			const MODULE: &'static [u8] = $module;
+			const NAME: &'static [u8] = $item;
+		}
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	storage_value! {
+		struct Answer(b"Test", b"Answer"): u32;
+	}
+
+	storage_map! {
+		struct Scores(b"Test", b"Scores"): map u32 => u64;
+	}
+
+	#[test]
+	fn keys_are_prefixed() {
+		// Well-known value, identical to FRAME's `System::Number`.
+		assert_eq!(
+			storage_prefix(b"System", b"Number").to_vec(),
+			hex_literal::hex!("26aa394eea5630e07c48ae0c9558cef702a5c1b19ab7a04f536c519aca4983ac")
+				.to_vec()
+		);
+
+		let key = Scores::hashed_key_for(&7);
+		assert!(key.starts_with(&Scores::prefix()));
+		assert!(key.ends_with(&7u32.encode()));
+		assert_eq!(key.len(), PREFIX_LEN + HASH_LEN + 4);
+	}
+
+	#[test]
+	fn values_and_maps_round_trip() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			assert_eq!(Answer::get(), None);
+			Answer::put(&42);
+			assert_eq!(Answer::get(), Some(42));
+			Answer::mutate(|v| *v = None);
+			assert!(!Answer::exists());
+
+			Scores::insert(&1, &10);
+			Scores::insert(&2, &20);
+			Scores::mutate(&2, |v| *v = v.map(|v| v + 1));
+			let mut scores = Scores::iter().collect::<Vec<_>>();
+			scores.sort();
+			assert_eq!(scores, vec![(1, 10), (2, 21)]);
+
+			Scores::clear();
+			assert_eq!(Scores::iter().count(), 0);
+		});
+	}
+
+	#[test]
+	fn transactions_roll_back_on_error() {
+		sp_io::TestExternalities::new_empty().execute_with(|| {
+			let _ = with_transaction(|| {
+				Answer::put(&1);
+				Err::<(), _>(())
+			});
+			assert_eq!(Answer::get(), None);
+
+			let _ = with_transaction(|| {
+				Answer::put(&2);
+				Ok::<_, ()>(())
+			});
+			assert_eq!(Answer::get(), Some(2));
+		});
+	}
+}
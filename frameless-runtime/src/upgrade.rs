@@ -0,0 +1,234 @@
+//! Runtime upgrades.
+//!
+//! New code is only written to `well_known_keys::CODE` once the host has confirmed it is a Wasm
+//! blob for this chain with a higher `spec_version`; anything else would brick the chain.
+//!
+//! Besides the immediate `Call::Upgrade`, an admin can `Call::AuthorizeUpgrade` a code hash to be
+//! enacted at a later block. Anyone may then upload the matching blob with
+//! `Call::ApplyAuthorizedUpgrade`, and it is enacted at the start of the scheduled block.
+
+use crate::{deposit_log, storage::StorageValue, BlockNumber, EMOJI, VERSION};
+use log::info;
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_core::H256;
+use sp_runtime::generic::DigestItem;
+use sp_std::prelude::*;
+use sp_version::RuntimeVersion;
+
+/// An upgrade authorized by an admin, waiting for its code.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub struct AuthorizedUpgrade {
+	/// `blake2_256` of the new code.
+	pub code_hash: H256,
+	/// The block at the start of which the new code is enacted.
+	pub enact_at: BlockNumber,
+}
+
+storage_value! {
+	/// The upgrade authorized by `Call::AuthorizeUpgrade`, if any.
+	pub struct Authorized(b"Upgrade", b"Authorized"): AuthorizedUpgrade;
+}
+
+storage_value! {
+	/// Code uploaded via `Call::ApplyAuthorizedUpgrade`, waiting for the authorized block.
+	pub struct PendingCode(b"Upgrade", b"PendingCode"): Vec<u8>;
+}
+
+/// Why a code blob was refused.
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum CodeError {
+	/// The host could not read a runtime version from the blob, so it is not valid Wasm.
+	InvalidWasm,
+	/// The blob is a runtime for some other chain.
+	SpecNameMismatch,
+	/// The blob would not move the chain forward.
+	SpecVersionNotIncreased,
+	/// No upgrade was authorized.
+	NotAuthorized,
+	/// The blob does not match the authorized code hash.
+	HashMismatch,
+	/// The authorized block has already passed.
+	TooLate,
+}
+
+/// Checks that `code` is a runtime this chain can upgrade to.
+pub fn check_code(code: &[u8]) -> Result<RuntimeVersion, CodeError> {
+	let version = sp_io::misc::runtime_version(code)
+		.and_then(|v| RuntimeVersion::decode(&mut &v[..]).ok())
+		.ok_or(CodeError::InvalidWasm)?;
+
+	if version.spec_name != VERSION.spec_name {
+		return Err(CodeError::SpecNameMismatch)
+	}
+	if version.spec_version <= VERSION.spec_version {
+		return Err(CodeError::SpecVersionNotIncreased)
+	}
+	Ok(version)
+}
+
+/// Checks `code` and, if acceptable, schedules it to replace the current runtime after this block.
+pub fn set_code(code: &[u8]) -> Result<(), CodeError> {
+	let version = check_code(code)?;
+	info!(
+		target: "upgrader",
+		"{EMOJI} upgrading to {} spec_version {}", version.spec_name, version.spec_version
+	);
+	crate::storage::tracking::note_write();
+	sp_io::storage::set(sp_storage::well_known_keys::CODE, code);
+	// Lets the node know it has to switch runtimes for the next block.
+	deposit_log(DigestItem::RuntimeEnvironmentUpdated);
+	Ok(())
+}
+
+/// Replaces any earlier authorization, discarding code uploaded for it.
+pub fn authorize_upgrade(
+	code_hash: H256,
+	enact_at: BlockNumber,
+	now: BlockNumber,
+) -> Result<(), CodeError> {
+	if enact_at <= now {
+		return Err(CodeError::TooLate)
+	}
+	info!(target: "upgrader", "{EMOJI} upgrade {:?} authorized for block {enact_at}", code_hash);
+	Authorized::put(&AuthorizedUpgrade { code_hash, enact_at });
+	PendingCode::kill();
+	Ok(())
+}
+
+/// Stores `code` if it matches the authorized hash, to be enacted by [`enact_authorized_upgrade`].
+pub fn apply_authorized_upgrade(code: Vec<u8>) -> Result<(), CodeError> {
+	let authorized = Authorized::get().ok_or(CodeError::NotAuthorized)?;
+	if H256(sp_io::hashing::blake2_256(&code)) != authorized.code_hash {
+		return Err(CodeError::HashMismatch)
+	}
+	check_code(&code)?;
+	info!(target: "upgrader", "{EMOJI} code uploaded, enacting at block {}", authorized.enact_at);
+	PendingCode::put(&code);
+	Ok(())
+}
+
+/// Enacts uploaded code once its block has come. Called from `initialize_block`.
+pub fn enact_authorized_upgrade(now: BlockNumber) {
+	match Authorized::get() {
+		Some(authorized) if authorized.enact_at <= now => (),
+		_ => return,
+	}
+	Authorized::kill();
+	match PendingCode::take() {
+		Some(code) =>
+			if let Err(e) = set_code(&code) {
+				info!(target: "upgrader", "{EMOJI} authorized upgrade failed: {:?}", e);
+			},
+		None => info!(target: "upgrader", "{EMOJI} authorized upgrade expired without code"),
+	}
+}
+
+/// Upgrades without an executor, for tests.
+#[cfg(test)]
+pub(crate) mod testing {
+	use super::*;
+	use sp_core::traits::{Externalities, ReadRuntimeVersion, ReadRuntimeVersionExt};
+
+	/// Stands in for the executor: a "Wasm blob" is just an encoded `RuntimeVersion`.
+	#[derive(Clone)]
+	pub(crate) struct EncodedVersion;
+
+	impl ReadRuntimeVersion for EncodedVersion {
+		fn read_runtime_version(
+			&self,
+			wasm_code: &[u8],
+			_ext: &mut dyn Externalities,
+		) -> Result<Vec<u8>, String> {
+			RuntimeVersion::decode(&mut &wasm_code[..])
+				.map(|v| v.encode())
+				.map_err(|e| e.to_string())
+		}
+	}
+
+	pub(crate) fn new_test_ext() -> sp_io::TestExternalities {
+		let mut ext = sp_io::TestExternalities::new_empty();
+		ext.register_extension(ReadRuntimeVersionExt::new(EncodedVersion));
+		ext
+	}
+
+	pub(crate) fn code(spec_name: &'static str, spec_version: u32) -> Vec<u8> {
+		RuntimeVersion { spec_name: spec_name.into(), spec_version, ..VERSION }.encode()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{testing::*, *};
+	use crate::{CurrentHeader, Header};
+	use sp_runtime::traits::Header as _;
+
+	#[test]
+	fn rejects_bad_code() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(set_code(b"wasm_blob"), Err(CodeError::InvalidWasm));
+			assert_eq!(
+				set_code(&code("another-runtime", VERSION.spec_version + 1)),
+				Err(CodeError::SpecNameMismatch)
+			);
+			assert_eq!(
+				set_code(&code("frameless-runtime", VERSION.spec_version)),
+				Err(CodeError::SpecVersionNotIncreased)
+			);
+			assert!(sp_io::storage::get(sp_storage::well_known_keys::CODE).is_none());
+		});
+	}
+
+	fn start_block(number: BlockNumber) {
+		CurrentHeader::put(&Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		));
+	}
+
+	#[test]
+	fn sets_code_and_deposits_digest() {
+		new_test_ext().execute_with(|| {
+			start_block(1);
+			let new_code = code("frameless-runtime", VERSION.spec_version + 1);
+
+			assert_eq!(set_code(&new_code), Ok(()));
+
+			assert_eq!(sp_io::storage::get(sp_storage::well_known_keys::CODE), Some(new_code));
+			assert_eq!(
+				CurrentHeader::get().unwrap().digest.logs,
+				vec![DigestItem::RuntimeEnvironmentUpdated]
+			);
+		});
+	}
+
+	#[test]
+	fn enacts_authorized_upgrade_at_block() {
+		new_test_ext().execute_with(|| {
+			let new_code = code("frameless-runtime", VERSION.spec_version + 1);
+			let code_hash = H256(sp_io::hashing::blake2_256(&new_code));
+
+			assert_eq!(apply_authorized_upgrade(new_code.clone()), Err(CodeError::NotAuthorized));
+			assert_eq!(authorize_upgrade(code_hash, 1, 1), Err(CodeError::TooLate));
+			assert_eq!(authorize_upgrade(code_hash, 3, 1), Ok(()));
+			assert_eq!(
+				apply_authorized_upgrade(code("frameless-runtime", VERSION.spec_version + 2)),
+				Err(CodeError::HashMismatch)
+			);
+			assert_eq!(apply_authorized_upgrade(new_code.clone()), Ok(()));
+
+			start_block(2);
+			enact_authorized_upgrade(2);
+			assert!(sp_io::storage::get(sp_storage::well_known_keys::CODE).is_none());
+
+			start_block(3);
+			enact_authorized_upgrade(3);
+			assert_eq!(sp_io::storage::get(sp_storage::well_known_keys::CODE), Some(new_code));
+			assert_eq!(Authorized::get(), None);
+			assert_eq!(PendingCode::get(), None);
+		});
+	}
+}
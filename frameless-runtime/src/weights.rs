@@ -0,0 +1,96 @@
+//! What dispatching each call costs, and how much of it fits in a block.
+//!
+//! The numbers are in `weights/generated.rs`. It still holds estimates, to be replaced by the
+//! output of a node built with `--features runtime-benchmarks`:
+//!
+//! ```sh
+//! node-template benchmark --output frameless-runtime/src/weights/generated.rs
+//! ```
+//!
+//! Blocks take extrinsics until their [`Weight::total_nanos`] reach [`MAXIMUM_BLOCK_NANOS`], see
+//! `apply_extrinsic`.
+
+use crate::Call;
+
+mod generated;
+
+/// The execution time of a call and its storage accesses, which on a real database can cost
+/// more than the execution itself.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Weight {
+	pub nanos: u64,
+	pub reads: u32,
+	pub writes: u32,
+}
+
+/// The time a storage read takes on RocksDB, like `frame_support`'s `RocksDbWeight`.
+pub const READ_NANOS: u64 = 25_000;
+/// The time a storage write takes on RocksDB, like `frame_support`'s `RocksDbWeight`.
+pub const WRITE_NANOS: u64 = 100_000;
+
+/// The time extrinsics may take per block: three quarters of the third of the slot left for
+/// execution, as on FRAME chains, with the rest for `initialize_block` and `finalize_block`.
+pub const MAXIMUM_BLOCK_NANOS: u64 = crate::SLOT_DURATION_MS * 1_000_000 / 3 * 3 / 4;
+
+/// What every signed extrinsic costs on top of its call: checking the signature, era, nonce and
+/// tip, and recording the extrinsic. An estimate, as this is not benchmarked.
+pub const EXTRINSIC_BASE: Weight = Weight { nanos: 100_000, reads: 4, writes: 5 };
+
+impl Weight {
+	/// The time the weight takes, with its storage accesses.
+	pub fn total_nanos(self) -> u64 {
+		self.nanos
+			.saturating_add(u64::from(self.reads).saturating_mul(READ_NANOS))
+			.saturating_add(u64::from(self.writes).saturating_mul(WRITE_NANOS))
+	}
+
+	pub fn saturating_add(self, other: Weight) -> Weight {
+		Weight {
+			nanos: self.nanos.saturating_add(other.nanos),
+			reads: self.reads.saturating_add(other.reads),
+			writes: self.writes.saturating_add(other.writes),
+		}
+	}
+}
+
+impl Call {
+	/// The weight of dispatching the call, including the calls of a batch. Calls which are only
+	/// dispatched later, once scheduled or approved, are not included.
+	pub fn weight(&self) -> Weight {
+		match self {
+			Call::Flip => generated::FLIP,
+			Call::Add(_) => generated::ADD,
+			Call::Multiply(_) => generated::MULTIPLY,
+			Call::Upgrade { payload, .. } =>
+				with_code(generated::UPGRADE, generated::UPGRADE_PER_KIB, payload),
+			Call::Kill { .. } => generated::KILL,
+			Call::AuthorizeUpgrade { .. } => generated::AUTHORIZE_UPGRADE,
+			Call::ApplyAuthorizedUpgrade { code } => with_code(
+				generated::APPLY_AUTHORIZED_UPGRADE,
+				generated::APPLY_AUTHORIZED_UPGRADE_PER_KIB,
+				code,
+			),
+			Call::Pause => generated::PAUSE,
+			Call::Resume => generated::RESUME,
+			Call::SetAdmins { .. } => generated::SET_ADMINS,
+			Call::SetAuthorities { .. } => generated::SET_AUTHORITIES,
+			Call::Propose { .. } => generated::PROPOSE,
+			Call::Approve { .. } => generated::APPROVE,
+			Call::Batch(calls) =>
+				calls.iter().fold(generated::BATCH, |w, c| w.saturating_add(c.weight())),
+			Call::BatchAll(calls) =>
+				calls.iter().fold(generated::BATCH_ALL, |w, c| w.saturating_add(c.weight())),
+			Call::Schedule { .. } => generated::SCHEDULE,
+			Call::CancelScheduled { .. } => generated::CANCEL_SCHEDULED,
+			Call::SetBlockReward { .. } => generated::SET_BLOCK_REWARD,
+			Call::Heartbeat { .. } => generated::HEARTBEAT,
+		}
+	}
+}
+
+/// `base` plus `nanos_per_kib` for every KiB of `code`: reading its version and storing it take
+/// longer the longer it is.
+fn with_code(base: Weight, nanos_per_kib: u64, code: &[u8]) -> Weight {
+	let nanos = nanos_per_kib.saturating_mul(code.len() as u64) / 1024;
+	base.saturating_add(Weight { nanos, ..Default::default() })
+}
@@ -0,0 +1,49 @@
+//! Estimates, not benchmarked: replace this file with the output of `node-template benchmark`.
+//!
+//! The storage accesses are counted from the code, and the times are rounded up from similar FRAME
+//! calls. Calls taking runtime code add 20µs per KiB for reading its version and storing it, and
+//! another 5µs for hashing it.
+
+use super::Weight;
+
+pub const FLIP: Weight = Weight { nanos: 10000, reads: 1, writes: 1 };
+
+pub const ADD: Weight = Weight { nanos: 10000, reads: 1, writes: 1 };
+
+pub const MULTIPLY: Weight = Weight { nanos: 10000, reads: 1, writes: 1 };
+
+pub const UPGRADE: Weight = Weight { nanos: 1000000, reads: 1, writes: 2 };
+
+pub const UPGRADE_PER_KIB: u64 = 20000;
+
+pub const KILL: Weight = Weight { nanos: 10000, reads: 1, writes: 1 };
+
+pub const AUTHORIZE_UPGRADE: Weight = Weight { nanos: 15000, reads: 1, writes: 2 };
+
+pub const APPLY_AUTHORIZED_UPGRADE: Weight = Weight { nanos: 1000000, reads: 1, writes: 1 };
+
+pub const APPLY_AUTHORIZED_UPGRADE_PER_KIB: u64 = 25000;
+
+pub const PAUSE: Weight = Weight { nanos: 10000, reads: 1, writes: 1 };
+
+pub const RESUME: Weight = Weight { nanos: 10000, reads: 0, writes: 1 };
+
+pub const SET_ADMINS: Weight = Weight { nanos: 15000, reads: 0, writes: 2 };
+
+pub const SET_AUTHORITIES: Weight = Weight { nanos: 20000, reads: 2, writes: 4 };
+
+pub const PROPOSE: Weight = Weight { nanos: 40000, reads: 5, writes: 2 };
+
+pub const APPROVE: Weight = Weight { nanos: 40000, reads: 5, writes: 1 };
+
+pub const BATCH: Weight = Weight { nanos: 5000, reads: 0, writes: 1 };
+
+pub const BATCH_ALL: Weight = Weight { nanos: 5000, reads: 0, writes: 1 };
+
+pub const SCHEDULE: Weight = Weight { nanos: 30000, reads: 2, writes: 4 };
+
+pub const CANCEL_SCHEDULED: Weight = Weight { nanos: 30000, reads: 2, writes: 3 };
+
+pub const SET_BLOCK_REWARD: Weight = Weight { nanos: 10000, reads: 0, writes: 1 };
+
+pub const HEARTBEAT: Weight = Weight { nanos: 20000, reads: 2, writes: 3 };
@@ -0,0 +1,35 @@
+//! The `benchmark` subcommand.
+
+use crate::{cli::BenchmarkCmd, execution::Executor};
+use node_template_runtime::benchmarking;
+use sc_executor::WasmExecutionMethod;
+
+impl BenchmarkCmd {
+	/// Benchmarks every call, printing the results and writing them to `output` if given.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		let code = match &self.wasm {
+			Some(path) => std::fs::read(path)?,
+			None => node_template_runtime::WASM_BINARY_BLOATY
+				.ok_or("The runtime was built without its Wasm, pass `--wasm`")?
+				.to_vec(),
+		};
+		let executor = Executor::new(WasmExecutionMethod::Interpreted, None, 1, 2);
+		let results = benchmarking::run(self.repeat, &code, executor);
+		println!("{:<24} {:>12} {:>6} {:>6}", "call", "nanos", "reads", "writes");
+		for result in &results {
+			println!(
+				"{:<24} {:>12} {:>6} {:>6}",
+				result.name, result.nanos, result.reads, result.writes
+			);
+			if let Some(nanos_per_kib) = result.nanos_per_kib {
+				println!("{:<24} {:>12}", "  per KiB of code", nanos_per_kib);
+			}
+		}
+
+		if let Some(output) = &self.output {
+			std::fs::write(output, benchmarking::weights_file(&results, self.repeat))?;
+			println!("Weights written to {}", output.display());
+		}
+		Ok(())
+	}
+}
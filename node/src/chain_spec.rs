@@ -1,5 +1,13 @@
-use node_template_runtime::GenesisConfig as FramelessGenesisConfig;
-use sc_service::ChainType;
+use crate::presets;
+use node_template_runtime::{
+	Address, GenesisConfig as FramelessGenesisConfig, GenesisOverrides, SS58_PREFIX,
+	TOKEN_DECIMALS, TOKEN_SYMBOL,
+};
+use sc_service::{ChainType, Properties};
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{bytes::to_hex, crypto::Ss58Codec, ed25519, sr25519, Pair, Public};
+use sp_finality_grandpa::AuthorityId as GrandpaId;
+use sp_runtime::{BuildStorage, Storage};
 
 // The URL for the telemetry server.
 // const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
@@ -7,68 +15,139 @@ use sc_service::ChainType;
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = sc_service::GenericChainSpec<FramelessGenesisConfig>;
 
-// /// Generate a crypto pair from seed.
-// pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Public {
-// 	TPublic::Pair::from_string(&format!("//{}", seed), None)
-// 		.expect("static values are valid; qed")
-// 		.public()
-// }
+/// Generate a crypto pair from seed.
+pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Public {
+	TPublic::Pair::from_string(&format!("//{}", seed), None)
+		.expect("static values are valid; qed")
+		.public()
+}
 
-// type AccountPublic = <Signature as Verify>::Signer;
+/// Generate an account address from seed.
+pub fn get_address_from_seed(seed: &str) -> Address {
+	Address::from(get_from_seed::<sr25519::Public>(seed).0)
+}
 
-// /// Generate an account ID from seed.
-// pub fn get_account_id_from_seed<TPublic: Public>(seed: &str) -> AccountId
-// where
-// 	AccountPublic: From<<TPublic::Pair as Pair>::Public>,
-// {
-// 	AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
-// }
+/// Generate an Aura authority key.
+pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
+	(get_from_seed::<AuraId>(s), get_from_seed::<GrandpaId>(s))
+}
 
-// /// Generate an Aura authority key.
-// pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
-// 	(get_from_seed::<AuraId>(s), get_from_seed::<GrandpaId>(s))
-// }
+/// The Aura and GRANDPA keys derived from the secret URI `suri`, e.g. `//Alice`.
+pub fn authority_keys_from_suri(suri: &str) -> Result<(AuraId, GrandpaId), String> {
+	let invalid = |e| format!("Invalid authority {}: {:?}", suri, e);
+	let aura = sr25519::Pair::from_string(suri, None).map_err(invalid)?;
+	let grandpa = ed25519::Pair::from_string(suri, None).map_err(invalid)?;
+	Ok((aura.public().into(), grandpa.public().into()))
+}
+
+/// The sr25519 account of the secret URI or SS58 address `who`.
+pub fn address_from_suri(who: &str) -> Result<Address, String> {
+	sr25519::Pair::from_string(who, None)
+		.map(|pair| pair.public())
+		.or_else(|_| sr25519::Public::from_ss58check(who))
+		.map(|public| Address::from(public.0))
+		.map_err(|_| format!("{} is neither a secret URI nor an SS58 address", who))
+}
 
 pub fn development_config() -> Result<ChainSpec, String> {
-	Ok(ChainSpec::from_genesis(
-		// Name
-		"Development",
-		// ID
-		"dev",
-		ChainType::Development,
-		move || FramelessGenesisConfig,
-		// Bootnodes
-		vec![],
-		// Telemetry
-		None,
-		// Protocol ID
-		None,
-		None,
-		// Properties
-		None,
-		// Extensions
-		None,
-	))
+	from_preset("Development", "dev", ChainType::Development, "dev")
 }
 
 pub fn local_testnet_config() -> Result<ChainSpec, String> {
-	Ok(ChainSpec::from_genesis(
-		// Name
-		"Local Testnet",
-		// ID
-		"local_testnet",
-		ChainType::Local,
-		move || FramelessGenesisConfig,
+	from_preset("Local Testnet", "local_testnet", ChainType::Local, "local")
+}
+
+/// The staging network, with the admins and authorities of `keys`, which it cannot do without.
+pub fn staging_testnet_config(keys: Option<GenesisOverrides>) -> Result<ChainSpec, String> {
+	let missing = || {
+		"The staging chain needs its admins and authorities, from --override-admin and \
+		 --override-authority"
+			.to_string()
+	};
+	let GenesisOverrides { admins, authorities } = keys.ok_or_else(missing)?;
+	let (admins, threshold) = admins.ok_or_else(missing)?;
+	let genesis = presets::staging(admins, threshold, authorities.ok_or_else(missing)?);
+	Ok(from_genesis("Staging Testnet", "staging_testnet", ChainType::Live, genesis))
+}
+
+/// The token and address format of the runtime, for wallets.
+fn properties() -> Properties {
+	let mut properties = Properties::new();
+	properties.insert("tokenSymbol".into(), TOKEN_SYMBOL.into());
+	properties.insert("tokenDecimals".into(), TOKEN_DECIMALS.into());
+	properties.insert("ss58Format".into(), SS58_PREFIX.into());
+	properties
+}
+
+fn from_preset(
+	name: &str,
+	id: &str,
+	chain_type: ChainType,
+	preset: &str,
+) -> Result<ChainSpec, String> {
+	let genesis = presets::preset(preset).ok_or_else(|| format!("Unknown preset {}", preset))?;
+	Ok(from_genesis(name, id, chain_type, genesis))
+}
+
+/// A chain spec starting from `genesis`.
+pub fn from_genesis(
+	name: &str,
+	id: &str,
+	chain_type: ChainType,
+	genesis: FramelessGenesisConfig,
+) -> ChainSpec {
+	ChainSpec::from_genesis(
+		name,
+		id,
+		chain_type,
+		move || genesis.clone(),
 		// Bootnodes
 		vec![],
 		// Telemetry
 		None,
 		// Protocol ID
 		None,
-		// Properties
-		None,
+		// Fork ID
 		None,
+		// Properties
+		Some(properties()),
 		// Extensions
 		None,
-	))
+	)
+}
+
+/// `spec` with `overrides` applied to its genesis state, which becomes raw.
+pub fn with_overrides(spec: ChainSpec, overrides: &GenesisOverrides) -> Result<ChainSpec, String> {
+	let mut storage = spec.build_storage()?;
+	overrides.assimilate_storage(&mut storage)?;
+	let json = with_storage(&spec, storage)?;
+	ChainSpec::from_json_bytes(json.to_string().into_bytes())
+}
+
+/// The JSON of `spec` with `storage` as its raw genesis. The spec keeps its name, boot nodes and
+/// properties.
+pub fn with_storage(
+	spec: &dyn sc_service::ChainSpec,
+	storage: Storage,
+) -> Result<serde_json::Value, String> {
+	let mut json: serde_json::Value =
+		serde_json::from_str(&spec.as_json(false)?).map_err(|e| e.to_string())?;
+	json["genesis"] = serde_json::json!({ "raw": raw_genesis(storage) });
+	Ok(json)
+}
+
+/// `storage` in the format of the `raw` genesis of chain spec files.
+fn raw_genesis(storage: Storage) -> serde_json::Value {
+	let hex_map = |entries: std::collections::BTreeMap<Vec<u8>, Vec<u8>>| {
+		entries
+			.into_iter()
+			.map(|(key, value)| (to_hex(&key, false), to_hex(&value, false).into()))
+			.collect::<serde_json::Map<_, _>>()
+	};
+	let children = storage
+		.children_default
+		.into_iter()
+		.map(|(key, child)| (to_hex(&key, false), hex_map(child.data).into()))
+		.collect::<serde_json::Map<_, _>>();
+	serde_json::json!({ "top": hex_map(storage.top), "childrenDefault": children })
 }
@@ -1,4 +1,8 @@
-use sc_cli::RunCmd;
+use crate::generate_spec::parse_endowment;
+use node_template_runtime::Balance;
+use sc_cli::{RunCmd, SharedParams};
+use sp_core::H256;
+use std::path::PathBuf;
 
 #[derive(Debug, clap::Parser)]
 pub struct Cli {
@@ -7,6 +11,27 @@ pub struct Cli {
 
 	#[clap(flatten)]
 	pub run: RunCmd,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub overrides: GenesisOverrideParams,
+}
+
+/// Changes to the genesis state of a chain spec file, e.g. one written by `export-state`, to start
+/// a new network from it. They also give the `staging` chain its admins and authorities.
+#[derive(Debug, clap::Parser)]
+pub struct GenesisOverrideParams {
+	/// Replace the admins with this secret URI or SS58 address. Can be repeated.
+	#[clap(long = "override-admin", value_name = "SURI")]
+	pub admins: Vec<String>,
+
+	/// How many of the overriding admins have to approve an admin call, one by default.
+	#[clap(long = "override-threshold", value_name = "COUNT")]
+	pub threshold: Option<u32>,
+
+	/// Replace the authorities with this secret URI, e.g. `//Alice`. Can be repeated.
+	#[clap(long = "override-authority", value_name = "SURI")]
+	pub authorities: Vec<String>,
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -18,6 +43,9 @@ pub enum Subcommand {
 	/// Build a chain specification.
 	BuildSpec(sc_cli::BuildSpecCmd),
 
+	/// Generate the chain specification of an ad-hoc network.
+	GenerateSpec(GenerateSpecCmd),
+
 	/// Validate blocks.
 	CheckBlock(sc_cli::CheckBlockCmd),
 
@@ -39,10 +67,143 @@ pub enum Subcommand {
 	/// Db meta columns information.
 	ChainInfo(sc_cli::ChainInfoCmd),
 
+	/// Benchmark every call and write the weights for the runtime.
+	Benchmark(BenchmarkCmd),
+
+	/// Dry-run a runtime upgrade against existing state.
+	TryUpgrade(TryUpgradeCmd),
+
+	/// Re-execute blocks, printing the storage changes of every extrinsic and the events.
+	Replay(ReplayCmd),
+
+	/// Generate the chain spec of a private network starting from the state of a live chain.
+	ForkOff(ForkOffCmd),
+
 	/// Custom -- extend it as you wish.
 	Custom(CustomCommand),
 }
 
+#[derive(Debug, clap::Parser)]
+pub struct GenerateSpecCmd {
+	/// The name of the network.
+	#[clap(long, default_value = "Ad-hoc Network")]
+	pub name: String,
+
+	/// The id of the network.
+	#[clap(long, default_value = "adhoc")]
+	pub id: String,
+
+	/// The secret URI of an authority, e.g. `//Alice`. Can be repeated.
+	#[clap(long = "authority", value_name = "SURI")]
+	pub authorities: Vec<String>,
+
+	/// The secret URI or SS58 address of an admin. Can be repeated.
+	#[clap(long = "admin", value_name = "SURI")]
+	pub admins: Vec<String>,
+
+	/// How many admins have to approve an admin call.
+	#[clap(long, default_value = "1")]
+	pub threshold: u32,
+
+	/// An account and its initial balance, e.g. `//Bob=1000`. Can be repeated.
+	#[clap(long = "endow", value_name = "SURI=AMOUNT", parse(try_from_str = parse_endowment))]
+	pub endowments: Vec<(String, Balance)>,
+
+	/// The initial flipper bit.
+	#[clap(long)]
+	pub bit: Option<bool>,
+
+	/// The initial adder/multiplier value.
+	#[clap(long)]
+	pub value: Option<u32>,
+
+	/// Minted for the author of every block.
+	#[clap(long, default_value = "0")]
+	pub block_reward: Balance,
+
+	/// Print the raw storage instead of the genesis config.
+	#[clap(long)]
+	pub raw: bool,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct BenchmarkCmd {
+	/// How many times to dispatch each call.
+	#[clap(long, default_value = "100")]
+	pub repeat: u32,
+
+	/// Where to write the weights, e.g. `frameless-runtime/src/weights/generated.rs`.
+	#[clap(long)]
+	pub output: Option<PathBuf>,
+
+	/// The uncompressed runtime Wasm to benchmark upgrades with, instead of the one the node
+	/// includes.
+	#[clap(long)]
+	pub wasm: Option<PathBuf>,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct TryUpgradeCmd {
+	/// The candidate runtime Wasm.
+	#[clap(long)]
+	pub wasm: PathBuf,
+
+	/// A state snapshot written by `export-state`, instead of the best block of the local
+	/// database. Only the migrations run against a snapshot, as it has no blocks.
+	#[clap(long)]
+	pub snapshot: Option<PathBuf>,
+
+	/// How many of the latest blocks of the local database to execute with the candidate.
+	#[clap(long, default_value = "10")]
+	pub blocks: u32,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct ReplayCmd {
+	/// The first block to replay.
+	#[clap(long, default_value = "1")]
+	pub from: u32,
+
+	/// The last block to replay, the best block if not given.
+	#[clap(long)]
+	pub to: Option<u32>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct ForkOffCmd {
+	/// The HTTP RPC endpoint of a node of the live chain.
+	#[clap(long, default_value = "http://localhost:9933")]
+	pub uri: String,
+
+	/// The block whose state to take, the latest finalized block if not given.
+	#[clap(long)]
+	pub at: Option<H256>,
+
+	/// The secret URI or SS58 address of the only admin of the fork.
+	#[clap(long, default_value = "//Alice")]
+	pub admin: String,
+
+	/// The secret URI of the only authority of the fork.
+	#[clap(long, default_value = "//Alice")]
+	pub authority: String,
+
+	/// Where to write the chain spec, instead of stdout.
+	#[clap(long)]
+	pub output: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+}
+
 #[derive(Debug, clap::Parser)]
 pub struct CustomCommand {
 	/// The salt to use in the transaction. If none is supplied, a "random" one will be chosen
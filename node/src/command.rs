@@ -1,11 +1,12 @@
 use crate::{
-	chain_spec,
-	cli::{Cli, Subcommand},
+	chain_spec::{self, address_from_suri, authority_keys_from_suri},
+	cli::{Cli, GenesisOverrideParams, Subcommand},
 	service,
 };
-use node_template_runtime::Block;
+use node_template_runtime::{Block, GenesisOverrides};
 use sc_cli::{ChainSpec, RuntimeVersion, SubstrateCli};
 use sc_service::PartialComponents;
+use sp_core::crypto::{set_default_ss58_version, Ss58AddressFormat};
 
 impl SubstrateCli for Cli {
 	fn impl_name() -> String {
@@ -33,11 +34,21 @@ impl SubstrateCli for Cli {
 	}
 
 	fn load_spec(&self, id: &str) -> Result<Box<dyn sc_service::ChainSpec>, String> {
+		let overrides = self.overrides.genesis_overrides()?;
 		Ok(match id {
+			// The dev presets are made of dev keys, overriding some of them is a mistake.
+			"dev" | "" | "local" if overrides.is_some() =>
+				return Err(format!("The {id:?} chain takes no --override-* flags")),
 			"dev" => Box::new(chain_spec::development_config()?),
 			"" | "local" => Box::new(chain_spec::local_testnet_config()?),
-			path =>
-				Box::new(chain_spec::ChainSpec::from_json_file(std::path::PathBuf::from(path))?),
+			"staging" => Box::new(chain_spec::staging_testnet_config(overrides)?),
+			path => {
+				let spec = chain_spec::ChainSpec::from_json_file(std::path::PathBuf::from(path))?;
+				match overrides {
+					Some(overrides) => Box::new(chain_spec::with_overrides(spec, &overrides)?),
+					None => Box::new(spec),
+				}
+			},
 		})
 	}
 
@@ -46,9 +57,31 @@ impl SubstrateCli for Cli {
 	}
 }
 
+impl GenesisOverrideParams {
+	/// The overrides given, if any.
+	fn genesis_overrides(&self) -> Result<Option<GenesisOverrides>, String> {
+		if self.admins.is_empty() && self.threshold.is_some() {
+			return Err("--override-threshold needs --override-admin".into())
+		}
+		let admins = (!self.admins.is_empty())
+			.then(|| {
+				let admins = self.admins.iter().map(|who| address_from_suri(who));
+				Ok((admins.collect::<Result<_, String>>()?, self.threshold.unwrap_or(1)))
+			})
+			.transpose()?;
+		let authorities = (!self.authorities.is_empty())
+			.then(|| self.authorities.iter().map(|suri| authority_keys_from_suri(suri)).collect())
+			.transpose()?;
+		Ok((admins.is_some() || authorities.is_some())
+			.then(|| GenesisOverrides { admins, authorities }))
+	}
+}
+
 /// Parse and run command line arguments
 pub fn run() -> sc_cli::Result<()> {
 	let cli = Cli::from_args();
+	// Keys and addresses are printed and parsed in the format of this chain.
+	set_default_ss58_version(Ss58AddressFormat::custom(node_template_runtime::SS58_PREFIX));
 
 	match &cli.subcommand {
 		Some(Subcommand::Key(cmd)) => cmd.run(&cli),
@@ -56,6 +89,7 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.sync_run(|config| cmd.run(config.chain_spec, config.network))
 		},
+		Some(Subcommand::GenerateSpec(cmd)) => cmd.run(),
 		Some(Subcommand::CheckBlock(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
@@ -106,6 +140,29 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.sync_run(|config| cmd.run::<Block>(&config))
 		},
+		#[cfg(feature = "runtime-benchmarks")]
+		Some(Subcommand::Benchmark(cmd)) => cmd.run(),
+		#[cfg(not(feature = "runtime-benchmarks"))]
+		Some(Subcommand::Benchmark(_)) => Err("Benchmarking wasn't enabled when building the node. \
+			You can enable it with `--features runtime-benchmarks`."
+			.into()),
+		#[cfg(feature = "try-runtime")]
+		Some(Subcommand::TryUpgrade(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| cmd.run(config))
+		},
+		#[cfg(not(feature = "try-runtime"))]
+		Some(Subcommand::TryUpgrade(_)) => Err("Trying upgrades wasn't enabled when building the \
+			node. You can enable it with `--features try-runtime`."
+			.into()),
+		Some(Subcommand::Replay(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| cmd.run(config))
+		},
+		Some(Subcommand::ForkOff(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| cmd.run(config))
+		},
 		Some(Subcommand::Custom(_)) => {
 			todo!()
 		},
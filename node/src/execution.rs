@@ -0,0 +1,43 @@
+//! Runtime calls on a state outside of a client, for the offline tools.
+
+use crate::service::ExecutorDispatch;
+use sc_executor::NativeElseWasmExecutor;
+use sc_service::Configuration;
+use sp_core::{testing::TaskExecutor, traits::RuntimeCode};
+use sp_runtime::traits::BlakeTwo256;
+use sp_state_machine::{Backend, ExecutionStrategy, OverlayedChanges, StateMachine};
+
+pub type Executor = NativeElseWasmExecutor<ExecutorDispatch>;
+
+pub fn executor(config: &Configuration) -> Executor {
+	Executor::new(
+		config.wasm_method,
+		config.default_heap_pages,
+		config.max_runtime_instances,
+		config.runtime_cache_size,
+	)
+}
+
+/// Calls `method` of `runtime_code` in Wasm on top of `state` and `overlay`, adding its changes
+/// to `overlay`.
+pub fn call<S: Backend<BlakeTwo256>>(
+	executor: &Executor,
+	state: &S,
+	overlay: &mut OverlayedChanges,
+	runtime_code: &RuntimeCode,
+	method: &str,
+	data: &[u8],
+) -> Result<Vec<u8>, String> {
+	StateMachine::new(
+		state,
+		overlay,
+		executor,
+		method,
+		data,
+		Default::default(),
+		runtime_code,
+		TaskExecutor::new(),
+	)
+	.execute(ExecutionStrategy::AlwaysWasm)
+	.map_err(|e| e.to_string())
+}
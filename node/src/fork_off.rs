@@ -0,0 +1,105 @@
+//! The `fork-off` subcommand: a chain spec starting from the state of a live chain, to reproduce
+//! its issues on a private network.
+//!
+//! The state of a finalized block is downloaded from the RPC of a running node of the live chain,
+//! page by page. Its admins and authorities are then replaced with test keys, `//Alice` by
+//! default, see [`GenesisOverrides`], and the spec given with `--chain` gets it as its raw genesis.
+//! Block numbers start over from zero on the fork, which forgets the old block hashes, scheduled
+//! calls and upgrades.
+
+use crate::{
+	chain_spec::{self, address_from_suri, authority_keys_from_suri},
+	cli::ForkOffCmd,
+};
+use jsonrpsee::{
+	core::{client::ClientT, Error as RpcError},
+	http_client::{HttpClient, HttpClientBuilder},
+	rpc_params,
+};
+use node_template_runtime::GenesisOverrides;
+use sc_cli::{CliConfiguration, SharedParams};
+use sc_service::Configuration;
+use sp_core::{
+	storage::{StorageData, StorageKey},
+	H256,
+};
+use sp_runtime::{BuildStorage, Storage};
+
+/// How many keys to download at once.
+const PAGE_SIZE: u32 = 1000;
+
+impl CliConfiguration for ForkOffCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
+
+fn rpc_error(e: RpcError) -> String {
+	format!("RPC request failed: {e}")
+}
+
+/// Every key and value of the state of block `at`.
+async fn download(client: &HttpClient, at: H256) -> Result<Storage, String> {
+	let mut storage = Storage::default();
+	let mut start: Option<StorageKey> = None;
+	loop {
+		let keys: Vec<StorageKey> = client
+			.request("state_getKeysPaged", rpc_params![StorageKey(vec![]), PAGE_SIZE, start, at])
+			.await
+			.map_err(rpc_error)?;
+		let values: Vec<Option<StorageData>> = client
+			.batch_request(
+				keys.iter().map(|key| ("state_getStorage", rpc_params![key, at])).collect(),
+			)
+			.await
+			.map_err(rpc_error)?;
+
+		let done = keys.len() < PAGE_SIZE as usize;
+		start = keys.last().cloned();
+		for (key, value) in keys.into_iter().zip(values) {
+			if let Some(value) = value {
+				storage.top.insert(key.0, value.0);
+			}
+		}
+		if done {
+			return Ok(storage)
+		}
+	}
+}
+
+impl ForkOffCmd {
+	/// Writes the chain spec of the fork.
+	pub fn run(&self, config: Configuration) -> sc_cli::Result<()> {
+		let client = HttpClientBuilder::default()
+			.max_request_body_size(u32::MAX)
+			.build(&self.uri)
+			.map_err(rpc_error)?;
+		let mut storage = config.tokio_handle.block_on(async {
+			let at = match self.at {
+				Some(at) => at,
+				None => client.request("chain_getFinalizedHead", None).await.map_err(rpc_error)?,
+			};
+			download(&client, at).await
+		})?;
+		eprintln!("Downloaded {} keys", storage.top.len());
+
+		let overrides = GenesisOverrides {
+			admins: Some((vec![address_from_suri(&self.admin)?], 1)),
+			authorities: Some(vec![authority_keys_from_suri(&self.authority)?]),
+		};
+		overrides.assimilate_storage(&mut storage)?;
+
+		let mut spec = chain_spec::with_storage(&*config.chain_spec, storage)?;
+		spec["name"] = format!("{} Fork", config.chain_spec.name()).into();
+		spec["id"] = format!("{}_fork", config.chain_spec.id()).into();
+		spec["chainType"] = "Local".into();
+		spec["bootNodes"] = serde_json::json!([]);
+
+		let json = serde_json::to_string_pretty(&spec).map_err(|e| e.to_string())?;
+		match &self.output {
+			Some(path) => std::fs::write(path, json)?,
+			None => println!("{}", json),
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,58 @@
+//! The `generate-spec` subcommand: a chain spec for an ad-hoc network, from command line flags
+//! instead of a preset.
+
+use crate::{
+	chain_spec::{self, address_from_suri, authority_keys_from_suri},
+	cli::GenerateSpecCmd,
+};
+use node_template_runtime::{Balance, GenesisConfig as FramelessGenesisConfig};
+use sc_service::ChainType;
+use sp_runtime::BuildStorage;
+
+/// Parses `SURI=AMOUNT`, e.g. `//Bob=1000`.
+pub fn parse_endowment(s: &str) -> Result<(String, Balance), String> {
+	let (who, amount) = s
+		.rsplit_once('=')
+		.ok_or_else(|| format!("{} is not of the form SURI=AMOUNT", s))?;
+	let amount = amount.parse().map_err(|e| format!("Invalid amount {}: {}", amount, e))?;
+	Ok((who.to_string(), amount))
+}
+
+impl GenerateSpecCmd {
+	/// Prints the chain spec.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		// A non-raw spec is only built when a node starts from it, too late to find out.
+		if self.authorities.is_empty() {
+			return Err("At least one --authority is needed".into())
+		}
+		if self.admins.is_empty() {
+			return Err("At least one --admin is needed".into())
+		}
+		let genesis = FramelessGenesisConfig {
+			admins: self
+				.admins
+				.iter()
+				.map(|who| address_from_suri(who))
+				.collect::<Result<_, _>>()?,
+			admin_threshold: self.threshold,
+			authorities: self
+				.authorities
+				.iter()
+				.map(|suri| authority_keys_from_suri(suri))
+				.collect::<Result<_, _>>()?,
+			balances: self
+				.endowments
+				.iter()
+				.map(|(who, amount)| Ok((address_from_suri(who)?, *amount)))
+				.collect::<Result<_, String>>()?,
+			bit: self.bit,
+			value: self.value,
+			block_reward: self.block_reward,
+		};
+		// Refuses what a node booting from the spec would, e.g. an unsatisfiable threshold.
+		genesis.build_storage()?;
+		let spec = chain_spec::from_genesis(&self.name, &self.id, ChainType::Local, genesis);
+		println!("{}", spec.as_json(self.raw)?);
+		Ok(())
+	}
+}
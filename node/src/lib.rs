@@ -1,2 +1,4 @@
 pub mod chain_spec;
+pub mod presets;
+pub mod rpc;
 pub mod service;
@@ -1,11 +1,21 @@
 //! Substrate Node Template CLI library.
 #![warn(missing_docs)]
 
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
 mod chain_spec;
 #[macro_use]
 mod service;
 mod cli;
 mod command;
+mod execution;
+mod fork_off;
+mod generate_spec;
+mod presets;
+mod replay;
+mod rpc;
+#[cfg(feature = "try-runtime")]
+mod try_upgrade;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
@@ -0,0 +1,83 @@
+//! The named genesis presets the node knows: `dev`, `local` and `staging`.
+//!
+//! `staging` is the only one meant for a live network, so it has no keys of its own.
+
+use crate::chain_spec::{authority_keys_from_seed, get_address_from_seed};
+use node_template_runtime::{Address, Balance, GenesisConfig as FramelessGenesisConfig, ADMIN_KEY};
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_finality_grandpa::AuthorityId as GrandpaId;
+
+/// The dev accounts endowed at genesis, along with the admins.
+const ENDOWED_SEEDS: [&str; 6] = ["Alice", "Bob", "Charlie", "Dave", "Eve", "Ferdie"];
+/// The balance of every endowed account.
+const ENDOWMENT: Balance = 1 << 60;
+/// One token, minted for the author of every block.
+const BLOCK_REWARD: Balance = 1_000_000_000_000;
+
+/// The genesis of the dev preset called `name`, if there is one.
+pub fn preset(name: &str) -> Option<FramelessGenesisConfig> {
+	match name {
+		"dev" => Some(dev()),
+		"local" => Some(local()),
+		_ => None,
+	}
+}
+
+/// Alice authors every block, and either Alice or Bob can dispatch admin calls alone.
+fn dev() -> FramelessGenesisConfig {
+	FramelessGenesisConfig {
+		admins: admins(&["Alice", "Bob"]),
+		admin_threshold: 1,
+		authorities: vec![authority_keys_from_seed("Alice")],
+		balances: dev_balances(),
+		bit: Some(false),
+		value: Some(1),
+		block_reward: BLOCK_REWARD,
+	}
+}
+
+/// Alice and Bob author blocks, and admin calls need both of them.
+fn local() -> FramelessGenesisConfig {
+	FramelessGenesisConfig {
+		admins: admins(&["Alice", "Bob"]),
+		admin_threshold: 2,
+		authorities: vec![authority_keys_from_seed("Alice"), authority_keys_from_seed("Bob")],
+		balances: dev_balances(),
+		bit: Some(false),
+		value: Some(1),
+		block_reward: BLOCK_REWARD,
+	}
+}
+
+/// The given authorities and admins, the latter being the only endowed accounts, so that no dev
+/// account has any say over the chain.
+pub fn staging(
+	admins: Vec<Address>,
+	admin_threshold: u32,
+	authorities: Vec<(AuraId, GrandpaId)>,
+) -> FramelessGenesisConfig {
+	FramelessGenesisConfig {
+		balances: admins.iter().map(|admin| (*admin, ENDOWMENT)).collect(),
+		admins,
+		admin_threshold,
+		authorities,
+		bit: Some(false),
+		value: Some(1),
+		block_reward: BLOCK_REWARD,
+	}
+}
+
+/// The legacy admin key and the given dev accounts.
+fn admins(seeds: &[&str]) -> Vec<Address> {
+	std::iter::once(Address::from(ADMIN_KEY))
+		.chain(seeds.iter().copied().map(get_address_from_seed))
+		.collect()
+}
+
+/// The legacy admin and the well-known dev accounts, all endowed.
+fn dev_balances() -> Vec<(Address, Balance)> {
+	std::iter::once(Address::from(ADMIN_KEY))
+		.chain(ENDOWED_SEEDS.into_iter().map(get_address_from_seed))
+		.map(|who| (who, ENDOWMENT))
+		.collect()
+}
@@ -0,0 +1,132 @@
+//! The `replay` subcommand: re-executes blocks of the local database with the runtime they were
+//! executed with, printing what each extrinsic changed and the events of each block.
+//!
+//! Nothing is written back to the database.
+
+use crate::{
+	cli::ReplayCmd,
+	execution::{self, Executor},
+	service,
+};
+use node_template_runtime::{inspect, opaque::Block, DispatchError};
+use parity_scale_codec::{Decode, Encode};
+use sc_cli::{CliConfiguration, SharedParams};
+use sc_client_api::{Backend, BlockBackend};
+use sc_service::{Configuration, PartialComponents};
+use sp_blockchain::HeaderBackend;
+use sp_core::{bytes::to_hex, traits::RuntimeCode};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{BlakeTwo256, Block as _, Header as _},
+	ApplyExtrinsicResult,
+};
+use sp_state_machine::{backend::BackendRuntimeCode, Backend as StateBackend, OverlayedChanges};
+
+impl CliConfiguration for ReplayCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
+
+/// `value` decoded as what is stored at `key` if known, hex otherwise.
+pub(crate) fn show(key: &[u8], value: Option<&[u8]>) -> String {
+	match value {
+		Some(value) => inspect::decode(key, value).unwrap_or_else(|| to_hex(value, false)),
+		None => "(none)".into(),
+	}
+}
+
+/// Prints the storage `after` changed since `before`, both on top of `state`.
+fn print_diff<S: StateBackend<BlakeTwo256>>(
+	state: &S,
+	before: &OverlayedChanges,
+	after: &OverlayedChanges,
+) -> sc_cli::Result<()> {
+	for (key, value) in after.changes() {
+		let old = match before.storage(key) {
+			Some(old) => old.map(<[u8]>::to_vec),
+			None => state.storage(key).map_err(|e| format!("{:?}", e))?,
+		};
+		let new = value.value();
+		if old.as_ref() == new {
+			continue
+		}
+		let name = inspect::name(key).unwrap_or_else(|| to_hex(key, false));
+		println!("    {name}: {} -> {}", show(key, old.as_deref()), show(key, new.map(|v| &v[..])));
+	}
+	Ok(())
+}
+
+/// Replays `block` on top of `state`, the state of its parent.
+fn replay<S: StateBackend<BlakeTwo256>>(
+	executor: &Executor,
+	state: &S,
+	block: Block,
+) -> sc_cli::Result<()> {
+	let code = BackendRuntimeCode::new(state);
+	let runtime_code: RuntimeCode = code.runtime_code()?;
+	let call = |overlay: &mut OverlayedChanges, method: &str, data: &[u8]| {
+		execution::call(executor, state, overlay, &runtime_code, method, data)
+	};
+
+	let (header, extrinsics) = block.deconstruct();
+	println!("block #{} {:?}", header.number(), header.hash());
+	let mut overlay = OverlayedChanges::default();
+	call(&mut overlay, "Core_initialize_block", &header.encode())?;
+	println!("  initialize_block");
+	print_diff(state, &OverlayedChanges::default(), &overlay)?;
+
+	for (index, extrinsic) in extrinsics.iter().enumerate() {
+		let before = overlay.clone();
+		let output = call(&mut overlay, "BlockBuilder_apply_extrinsic", &extrinsic.encode())?;
+		let result = ApplyExtrinsicResult::decode(&mut &output[..]).map_err(|e| e.to_string())?;
+		match result {
+			Ok(Ok(())) => println!("  extrinsic {index}: ok"),
+			Ok(Err(error)) => {
+				// Asked of a copy, so that the explanation leaves no trace.
+				let explained =
+					call(&mut overlay.clone(), "ErrorApi_decode_error", &error.encode())?;
+				match Option::<DispatchError>::decode(&mut &explained[..]).ok().flatten() {
+					Some(error) => println!("  extrinsic {index}: failed: {:?}", error),
+					None => println!("  extrinsic {index}: failed: {:?}", error),
+				}
+			},
+			Err(invalid) => println!("  extrinsic {index}: invalid: {:?}", invalid),
+		}
+		print_diff(state, &before, &overlay)?;
+	}
+
+	let before = overlay.clone();
+	call(&mut overlay, "BlockBuilder_finalize_block", &[])?;
+	println!("  finalize_block");
+	print_diff(state, &before, &overlay)?;
+
+	let events_key = inspect::events_key();
+	let events = match overlay.storage(&events_key) {
+		Some(events) => events.map(<[u8]>::to_vec),
+		None => state.storage(&events_key).map_err(|e| format!("{:?}", e))?,
+	};
+	for event in events.and_then(|events| inspect::decode_events(&events)).unwrap_or_default() {
+		println!("  event {event}");
+	}
+	Ok(())
+}
+
+impl ReplayCmd {
+	/// Replays the blocks, printing what happened.
+	pub fn run(&self, config: Configuration) -> sc_cli::Result<()> {
+		let executor = execution::executor(&config);
+		let PartialComponents { client, backend, .. } = service::new_partial(&config)?;
+		let to = self.to.unwrap_or_else(|| client.info().best_number);
+
+		for number in self.from.max(1)..=to {
+			let block = client
+				.block(&BlockId::Number(number))?
+				.ok_or_else(|| format!("block #{number} is not in the database"))?
+				.block;
+			let state = backend.state_at(&BlockId::Hash(*block.header.parent_hash()))?;
+			replay(&executor, &state, block)?;
+		}
+		Ok(())
+	}
+}
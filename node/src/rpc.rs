@@ -0,0 +1,224 @@
+//! The node's own RPC methods, on top of the ones every Substrate node has.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use jsonrpsee::{
+	core::{Error as JsonRpseeError, RpcResult},
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+	RpcModule,
+};
+use node_template_runtime::{
+	apis::AccountApi,
+	history::{self, HistoryRecord},
+	opaque::Block,
+	AccountInfo, Address, Nonce, SS58_PREFIX,
+};
+use parity_scale_codec::{Decode, Encode};
+use sc_client_api::{Backend, StorageProvider};
+use sc_transaction_pool_api::{InPoolTransaction, TransactionPool};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	crypto::{AccountId32, Ss58Codec},
+	offchain::{OffchainStorage, STORAGE_PREFIX},
+	storage::StorageKey,
+};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+/// The most nonces one `flipper_history` request looks up.
+pub const MAX_HISTORY_PAGE: u32 = 1000;
+
+/// Flipper specific RPC methods. Accounts are given as SS58 addresses in the runtime's
+/// `SS58_PREFIX`, like wallets show them, or as hex.
+#[rpc(server)]
+pub trait FlipperApi<BlockHash> {
+	/// The `Add` and `Multiply` calls signed by `who` up to block `at`, or the best block, oldest
+	/// first. Calls in blocks imported without offchain indexing are missing.
+	///
+	/// Only the calls signed with the `limit` nonces from `from` on are returned, by default the
+	/// first [`MAX_HISTORY_PAGE`] nonces, which is also the largest `limit` allowed. The next page
+	/// starts at `from + limit`, up to the nonce `flipper_accountInfo` reports.
+	#[method(name = "flipper_history")]
+	fn history(
+		&self,
+		who: String,
+		at: Option<BlockHash>,
+		from: Option<Nonce>,
+		limit: Option<u32>,
+	) -> RpcResult<Vec<HistoryRecord>>;
+}
+
+/// The account RPC methods, which only need the state and the transaction pool. Accounts are
+/// given like for [`FlipperApiServer`].
+#[rpc(server)]
+pub trait AccountsApi<BlockHash> {
+	/// The nonce, balances and admin status of `who` at block `at`, or the best block.
+	#[method(name = "flipper_accountInfo")]
+	fn account_info(&self, who: String, at: Option<BlockHash>) -> RpcResult<AccountInfo>;
+
+	/// The nonce the next extrinsic of `who` has to use, after the ones ready in the transaction
+	/// pool, like the `system_accountNextIndex` of FRAME chains.
+	#[method(name = "system_accountNextIndex", aliases = ["account_nextIndex"])]
+	fn next_index(&self, who: String) -> RpcResult<Nonce>;
+}
+
+/// Reads the history the runtime indexed into the offchain database.
+pub struct Flipper<C, B: Backend<Block>> {
+	client: Arc<C>,
+	offchain: B::OffchainStorage,
+	_backend: PhantomData<B>,
+}
+
+impl<C, B: Backend<Block>> Flipper<C, B> {
+	/// Serves the history in `offchain`, bounded by the nonces in the state of `client`.
+	pub fn new(client: Arc<C>, offchain: B::OffchainStorage) -> Self {
+		Self { client, offchain, _backend: PhantomData }
+	}
+}
+
+/// Reads the accounts.
+pub struct Accounts<C, P> {
+	client: Arc<C>,
+	pool: Arc<P>,
+}
+
+impl<C, P> Accounts<C, P> {
+	/// Serves the accounts in the state of `client` and `pool`.
+	pub fn new(client: Arc<C>, pool: Arc<P>) -> Self {
+		Self { client, pool }
+	}
+}
+
+fn error(message: &str, e: impl std::fmt::Debug) -> JsonRpseeError {
+	CallError::Custom(ErrorObject::owned(1, message, Some(format!("{:?}", e)))).into()
+}
+
+/// The account of `who`, an SS58 address in [`SS58_PREFIX`] or a hex public key.
+fn parse_account(who: &str) -> RpcResult<Address> {
+	if let Some(hex) = who.strip_prefix("0x") {
+		return hex.parse().map_err(|e| error("Invalid account.", e))
+	}
+	let (account, format) =
+		AccountId32::from_ss58check_with_version(who).map_err(|e| error("Invalid account.", e))?;
+	if u16::from(format) != SS58_PREFIX {
+		return Err(error("Invalid account.", format!("not an address of prefix {SS58_PREFIX}")))
+	}
+	Ok(Address::from(<[u8; 32]>::from(account)))
+}
+
+impl<C, B> FlipperApiServer<<Block as BlockT>::Hash> for Flipper<C, B>
+where
+	C: HeaderBackend<Block> + StorageProvider<Block, B>,
+	C: Send + Sync + 'static,
+	B: Backend<Block> + Send + Sync + 'static,
+{
+	fn history(
+		&self,
+		who: String,
+		at: Option<<Block as BlockT>::Hash>,
+		from: Option<Nonce>,
+		limit: Option<u32>,
+	) -> RpcResult<Vec<HistoryRecord>> {
+		let who = parse_account(&who)?;
+		let limit = limit.unwrap_or(MAX_HISTORY_PAGE);
+		if limit > MAX_HISTORY_PAGE {
+			return Err(error("The limit is too large.", format!("at most {MAX_HISTORY_PAGE}")))
+		}
+		let from = from.unwrap_or(0);
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		// Every extrinsic `who` signed used one of the nonces below its current one.
+		let nonce = self
+			.client
+			.storage(&at, &StorageKey(history::nonce_key(&who)))
+			.map_err(|e| error("Unable to read the nonce.", e))?
+			.map(|data| Nonce::decode(&mut &data.0[..]))
+			.transpose()
+			.map_err(|e| error("Unable to decode the nonce.", e))?
+			.unwrap_or(0);
+
+		(from..nonce.min(from.saturating_add(limit)))
+			.filter_map(|nonce| {
+				self.offchain.get(STORAGE_PREFIX, &history::history_key(&who, nonce))
+			})
+			.map(|record| {
+				HistoryRecord::decode(&mut &record[..])
+					.map_err(|e| error("Unable to decode a history record.", e))
+			})
+			.collect()
+	}
+}
+
+impl<C, P> AccountsApiServer<<Block as BlockT>::Hash> for Accounts<C, P>
+where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	C: Send + Sync + 'static,
+	C::Api: AccountApi<Block>,
+	P: TransactionPool<Block = Block> + 'static,
+{
+	fn account_info(
+		&self,
+		who: String,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> RpcResult<AccountInfo> {
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client
+			.runtime_api()
+			.account_info(&at, parse_account(&who)?)
+			.map_err(|e| error("Unable to query the account.", e))
+	}
+
+	fn next_index(&self, who: String) -> RpcResult<Nonce> {
+		let mut nonce = self.account_info(who.clone(), None)?.nonce;
+		let who = parse_account(&who)?;
+		// Ready transactions come in nonce order, each providing the tag of its signer and nonce.
+		for transaction in self.pool.ready() {
+			if transaction.provides().first() == Some(&(who, nonce).encode()) {
+				nonce += 1;
+			}
+		}
+		Ok(nonce)
+	}
+}
+
+/// The RPC methods of a full node.
+pub fn create_full<C, B, P>(
+	client: Arc<C>,
+	backend: Arc<B>,
+	pool: Arc<P>,
+) -> Result<RpcModule<()>, Box<dyn std::error::Error + Send + Sync>>
+where
+	C: HeaderBackend<Block> + StorageProvider<Block, B> + ProvideRuntimeApi<Block>,
+	C: Send + Sync + 'static,
+	C::Api: AccountApi<Block>,
+	B: Backend<Block> + Send + Sync + 'static,
+	P: TransactionPool<Block = Block> + 'static,
+{
+	let mut module = RpcModule::new(());
+	module.merge(Accounts::new(client.clone(), pool).into_rpc())?;
+	// Only the history needs the offchain database.
+	if let Some(offchain) = backend.offchain_storage() {
+		module.merge(Flipper::<C, B>::new(client, offchain).into_rpc())?;
+	}
+	Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{crypto::Ss58AddressFormat, sr25519, Pair};
+
+	#[test]
+	fn parses_ss58_and_hex_accounts() {
+		let alice = sr25519::Pair::from_string("//Alice", None).unwrap().public();
+		let address = alice.to_ss58check_with_version(Ss58AddressFormat::custom(SS58_PREFIX));
+		assert_eq!(parse_account(&address).unwrap(), Address::from(alice.0));
+		assert_eq!(
+			parse_account(&format!("{:?}", Address::from(alice.0))).unwrap(),
+			Address::from(alice.0)
+		);
+		// The generic Substrate prefix is another chain's.
+		assert!(parse_account(&alice.to_ss58check()).is_err());
+		assert!(parse_account("alice").is_err());
+	}
+}
@@ -158,6 +158,9 @@ fn remote_keystore(_url: &String) -> Result<Arc<LocalKeystore>, &'static str> {
 
 /// Builds a new service for a full client.
 pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError> {
+	// The runtime indexes the history `flipper_history` serves.
+	config.offchain_worker.indexing_enabled = true;
+
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -221,13 +224,23 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
 
+	let rpc_builder = {
+		let client = client.clone();
+		let backend = backend.clone();
+		let pool = transaction_pool.clone();
+		Box::new(move |_, _| {
+			crate::rpc::create_full(client.clone(), backend.clone(), pool.clone())
+				.map_err(Into::into)
+		})
+	};
+
 	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
 		network: network.clone(),
 		client: client.clone(),
 		keystore: keystore_container.sync_keystore(),
 		task_manager: &mut task_manager,
 		transaction_pool: transaction_pool.clone(),
-		rpc_builder: Box::new(|_, _| Ok(jsonrpsee::RpcModule::new(()))),
+		rpc_builder,
 		backend,
 		system_rpc_tx,
 		config,
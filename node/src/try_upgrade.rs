@@ -0,0 +1,225 @@
+//! The `try-upgrade` subcommand: a dry run of a runtime upgrade against existing state.
+//!
+//! The candidate Wasm is installed over the code of the state in an overlay, so nothing is ever
+//! written back. It then runs the pending migrations on the best state, and executes the latest
+//! blocks each on top of the state of its parent with the migrations already applied. What the
+//! candidate changed in a block is compared key by key with what the chain's own runtime changed,
+//! leaving out the keys the migrations wrote. Any difference or panic fails the dry run.
+//!
+//! The candidate has to be built with the `try-runtime` feature, for its `TryRuntime` API, which
+//! executes past blocks without checking signatures.
+
+use crate::{
+	chain_spec::ChainSpec,
+	cli::TryUpgradeCmd,
+	execution::{self, Executor},
+	replay::show,
+	service,
+};
+use node_template_runtime::{inspect, opaque::Block};
+use parity_scale_codec::{Decode, Encode};
+use sc_cli::{CliConfiguration, SharedParams};
+use sc_client_api::{Backend, BlockBackend};
+use sc_service::{Configuration, PartialComponents};
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	bytes::to_hex,
+	storage::well_known_keys,
+	traits::{RuntimeCode, WrappedRuntimeCode},
+};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{BlakeTwo256, Header as _},
+	BuildStorage, RuntimeString, StateVersion,
+};
+use sp_state_machine::{
+	Backend as StateBackend, InMemoryBackend, OverlayedChanges, StorageTransactionCache,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+impl CliConfiguration for TryUpgradeCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
+
+/// The runtime being tried.
+struct Candidate {
+	code: WrappedRuntimeCode<'static>,
+	hash: Vec<u8>,
+}
+
+impl Candidate {
+	fn read(path: &std::path::Path) -> sc_cli::Result<Self> {
+		let code = std::fs::read(path)?;
+		let hash = sp_core::blake2_256(&code).to_vec();
+		Ok(Self { code: WrappedRuntimeCode(code.into()), hash })
+	}
+
+	/// An overlay replacing the code of the state with the candidate.
+	fn overlay(&self) -> OverlayedChanges {
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_storage(well_known_keys::CODE.to_vec(), Some(self.code.0.to_vec()));
+		overlay
+	}
+
+	/// Calls `method` on top of `state` and `overlay`, adding its changes to `overlay`.
+	fn call<S: StateBackend<BlakeTwo256>>(
+		&self,
+		executor: &Executor,
+		state: &S,
+		overlay: &mut OverlayedChanges,
+		method: &str,
+		data: &[u8],
+	) -> Result<Vec<u8>, String> {
+		let runtime_code =
+			RuntimeCode { code_fetcher: &self.code, heap_pages: None, hash: self.hash.clone() };
+		execution::call(executor, state, overlay, &runtime_code, method, data)
+	}
+
+	/// Runs the pending migrations on top of `state`, returning the names of the ones that ran.
+	fn migrate<S: StateBackend<BlakeTwo256>>(
+		&self,
+		executor: &Executor,
+		state: &S,
+		overlay: &mut OverlayedChanges,
+	) -> Result<Vec<RuntimeString>, String> {
+		let output = self.call(executor, state, overlay, "TryRuntime_on_runtime_upgrade", &[])?;
+		Ok(Vec::<RuntimeString>::decode(&mut &output[..]).unwrap_or_default())
+	}
+}
+
+/// Runs the pending migrations on top of `state`, returning whether they went through.
+fn try_migrations<S: StateBackend<BlakeTwo256>>(
+	executor: &Executor,
+	candidate: &Candidate,
+	state: &S,
+) -> bool {
+	let (before, _) = state.storage_root(std::iter::empty(), StateVersion::V1);
+	let mut overlay = candidate.overlay();
+	match candidate.migrate(executor, state, &mut overlay) {
+		Ok(ran) => {
+			let after = overlay.storage_root(
+				state,
+				&mut StorageTransactionCache::default(),
+				StateVersion::V1,
+			);
+			println!("migrations: {:?}", ran);
+			println!("  state root {:?} -> {:?}", before, after);
+			true
+		},
+		Err(e) => {
+			println!("migrations: FAILED, panicked: {e}");
+			false
+		},
+	}
+}
+
+/// The keys whose values differ between `before` and `after`.
+fn changed_keys<S: StateBackend<BlakeTwo256>>(before: &S, after: &S) -> BTreeSet<Vec<u8>> {
+	let before: BTreeMap<_, _> = before.pairs().into_iter().collect();
+	let after: BTreeMap<_, _> = after.pairs().into_iter().collect();
+	before
+		.keys()
+		.chain(after.keys())
+		.filter(|key| before.get(*key) != after.get(*key))
+		.cloned()
+		.collect()
+}
+
+/// Executes `block` with the candidate on top of the state of its parent with the migrations
+/// applied, and compares what it changed with the state the chain's own runtime left, `state`.
+/// Returns whether the candidate changed the same keys the same way.
+///
+/// Signatures are not checked: they cover the `spec_version` the extrinsics were signed for, which
+/// the candidate does not have.
+fn try_block<S: StateBackend<BlakeTwo256>>(
+	executor: &Executor,
+	candidate: &Candidate,
+	parent_state: &S,
+	state: &S,
+	block: Block,
+) -> sc_cli::Result<bool> {
+	let number = *block.header.number();
+	let mut overlay = candidate.overlay();
+	if let Err(e) = candidate.migrate(executor, parent_state, &mut overlay) {
+		println!("block #{number}: FAILED, migrations panicked: {e}");
+		return Ok(false)
+	}
+	let migrated: BTreeSet<_> = overlay.changes().map(|(key, _)| key.clone()).collect();
+	let data = (&block, false).encode();
+	if let Err(e) =
+		candidate.call(executor, parent_state, &mut overlay, "TryRuntime_execute_block", &data)
+	{
+		println!("block #{number}: FAILED, panicked: {e}");
+		return Ok(false)
+	}
+
+	let mut keys = changed_keys(parent_state, state);
+	keys.extend(overlay.changes().map(|(key, _)| key.clone()));
+	let mut differences = Vec::new();
+	for key in keys.difference(&migrated) {
+		let got = match overlay.storage(key) {
+			Some(got) => got.map(<[u8]>::to_vec),
+			None => parent_state.storage(key).map_err(|e| format!("{:?}", e))?,
+		};
+		let expected = state.storage(key).map_err(|e| format!("{:?}", e))?;
+		if got != expected {
+			differences.push((key, expected, got));
+		}
+	}
+
+	if differences.is_empty() {
+		println!("block #{number}: ok, same changes as the chain");
+		return Ok(true)
+	}
+	println!("block #{number}: FAILED, {} keys differ from the chain:", differences.len());
+	for (key, expected, got) in differences {
+		let name = inspect::name(key).unwrap_or_else(|| to_hex(key, false));
+		println!(
+			"    {name}: chain has {}, candidate got {}",
+			show(key, expected.as_deref()),
+			show(key, got.as_deref())
+		);
+	}
+	Ok(false)
+}
+
+impl TryUpgradeCmd {
+	/// Runs the dry run, printing what happened, and fails if anything went wrong.
+	pub fn run(&self, config: Configuration) -> sc_cli::Result<()> {
+		let candidate = Candidate::read(&self.wasm)?;
+		let executor = execution::executor(&config);
+
+		if let Some(snapshot) = &self.snapshot {
+			let storage = ChainSpec::from_json_file(snapshot.clone())?.build_storage()?;
+			let state = InMemoryBackend::<BlakeTwo256>::from((storage, StateVersion::V1));
+			if !try_migrations(&executor, &candidate, &state) {
+				return Err("The migrations failed".into())
+			}
+			return Ok(())
+		}
+
+		let PartialComponents { client, backend, .. } = service::new_partial(&config)?;
+		let info = client.info();
+		let best_state = backend.state_at(&BlockId::Hash(info.best_hash))?;
+		let mut failures = usize::from(!try_migrations(&executor, &candidate, &best_state));
+
+		let first = (info.best_number + 1).saturating_sub(self.blocks).max(1);
+		for number in first..=info.best_number {
+			let block = client
+				.block(&BlockId::Number(number))?
+				.ok_or_else(|| format!("block #{number} is not in the database"))?
+				.block;
+			let parent_state = backend.state_at(&BlockId::Hash(*block.header.parent_hash()))?;
+			let state = backend.state_at(&BlockId::Hash(block.header.hash()))?;
+			if !try_block(&executor, &candidate, &parent_state, &state, block)? {
+				failures += 1;
+			}
+		}
+		if failures > 0 {
+			return Err(format!("The upgrade failed {failures} of its checks").into())
+		}
+		Ok(())
+	}
+}